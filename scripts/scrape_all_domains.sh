@@ -10,7 +10,10 @@ output="$1"
 
 for domain in $(python3 spider --list-domains)
 do
-	if [ ! -e "$output/$domain" ]
+	# Interrupted crawls are started again. Older crawls without a crawl state
+	# are skipped like finished ones.
+	state="$output/$domain/crawl_state.json"
+	if [ ! -e "$output/$domain" ] || { [ -e "$state" ] && ! grep -q '"complete": *true' "$state"; }
 	then
 		echo "$domain"
 		python3 spider "$domain" "$output"
@@ -0,0 +1,81 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use mini_search_engine::crawl_state::{self, CRAWL_STATE_FILE};
+use mini_search_engine::jsonl::{JsonlWriter, PageLine};
+use mini_search_engine::webpage::Webpage;
+
+// Converts crawler output with one JSON file per page to one JSON Lines file
+// per domain, which keeps URLs as they were crawled instead of in file names
+fn main() -> std::io::Result<()> {
+	let mut args = std::env::args();
+	let program = args.next().unwrap_or("convert".to_string());
+	let usage = || -> ! {
+		eprintln!("Usage: {} [--format jsonl|jsonl.gz|jsonl.zst] CRAWLER_OUTPUT OUTPUT_DIR", program);
+		std::process::exit(1);
+	};
+
+	let mut format = "jsonl.zst".to_string();
+	let mut paths = Vec::new();
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--format" => {
+				format = args.next().filter(|f| ["jsonl", "jsonl.gz", "jsonl.zst"].contains(&f.as_str())).unwrap_or_else(|| usage());
+			},
+			_ => paths.push(arg),
+		}
+	}
+	let [input_path, output_path] = <[String; 2]>::try_from(paths).unwrap_or_else(|_| usage());
+	let output_path = Path::new(&output_path);
+	fs::create_dir_all(output_path)?;
+
+	let mut dirs: Vec<PathBuf> = fs::read_dir(&input_path)?.map(|e| e.map(|e| e.path())).collect::<Result<_, _>>()?;
+	dirs.retain(|d| d.is_dir());
+	dirs.sort_unstable();
+	for dir in dirs {
+		let domain = dir.file_name().unwrap().to_string_lossy().to_string();
+		let (page_count, error_count) = convert_domain(&dir, &output_path.join(format!("{}.{}", domain, format)))?;
+		let state_path = dir.join(CRAWL_STATE_FILE);
+		if state_path.exists() {
+			fs::copy(state_path, crawl_state::get_domain_state_path(output_path, &domain))?;
+		}
+		if error_count > 0 {
+			println!("{}: {} pages, skipped {} pages with errors", domain, page_count, error_count);
+		} else {
+			println!("{}: {} pages", domain, page_count);
+		}
+	}
+	Ok(())
+}
+
+// Returns the number of pages converted and skipped
+fn convert_domain(dir: &Path, output_path: &Path) -> std::io::Result<(usize, usize)> {
+	let mut paths: Vec<PathBuf> = fs::read_dir(dir)?.map(|e| e.map(|e| e.path())).collect::<Result<_, _>>()?;
+	paths.retain(|p| p.extension().is_some_and(|e| e == "json") && !p.ends_with(CRAWL_STATE_FILE));
+	paths.sort_unstable();
+
+	let mut writer = JsonlWriter::create(output_path)?;
+	let mut page_count = 0;
+	let mut error_count = 0;
+	for path in paths {
+		let mut webpage: Webpage = match fs::read_to_string(&path).map_err(|e| e.to_string())
+			.and_then(|c| serde_json::from_str(&c).map_err(|e| e.to_string()))
+		{
+			Ok(webpage) => webpage,
+			Err(e) => {
+				eprintln!("Skipping {}: {}", path.display(), e);
+				error_count += 1;
+				continue
+			},
+		};
+		// Pages from older crawls only have their URL in the file name
+		if webpage.url.is_empty() {
+			let name = path.file_name().unwrap().to_string_lossy();
+			webpage.url = name.strip_suffix(".json").unwrap().replace("%2F", "/");
+		}
+		let html = fs::read(path.with_extension("html")).ok().map(|h| String::from_utf8_lossy(&h).into_owned());
+		writer.write(&PageLine {webpage, html})?;
+		page_count += 1;
+	}
+	writer.finish()?;
+	Ok((page_count, error_count))
+}
@@ -0,0 +1,740 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::Read;
+use std::path::Path;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+use url::Url;
+use mini_search_engine::crawl_state::{CrawlState, CachedPage};
+use mini_search_engine::extract::{self, ContentRules, ExtractRules};
+use mini_search_engine::robots::Robots;
+use mini_search_engine::sitemap::{self, Sitemap};
+use mini_search_engine::warc::{self, WarcWriter};
+use mini_search_engine::webpage::Webpage;
+
+// Pages larger than this are cut off
+const MAX_PAGE_SIZE: u64 = 16 * 1024 * 1024;
+
+// Limit on the size of sitemaps from the sitemaps protocol
+const MAX_SITEMAP_SIZE: u64 = 50 * 1024 * 1024;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+// How often the crawl state is saved
+const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+// Number of crawls in a row a page must be missing from before its saved copy
+// is deleted, since pages are sometimes removed by mistake and put back
+const MAX_MISSING: u32 = 3;
+
+// Waits after a 429 or 503 response without a Retry-After header, doubled
+// after each attempt
+const INITIAL_BACKOFF: Duration = Duration::from_secs(10);
+const MAX_BACKOFF: Duration = Duration::from_secs(15 * 60);
+
+// The domains to crawl, shared with the Python spider. Each domain has URL
+// prefixes which pages must start with, and prefixes they must not start with.
+#[derive(serde::Deserialize)]
+struct Config {
+	whitelists: HashMap<String, Vec<String>>,
+
+	#[serde(default)]
+	blacklists: HashMap<String, Vec<String>>,
+
+	// Domains that need to be crawled more slowly than the defaults
+	#[serde(default)]
+	limits: HashMap<String, Limits>,
+
+	// Sitemaps to start from, besides the ones listed in robots.txt
+	#[serde(default)]
+	sitemaps: HashMap<String, Vec<String>>,
+}
+
+// How the crawler behaves on each host of a domain
+#[derive(serde::Deserialize, Clone)]
+#[serde(default)]
+struct Limits {
+	user_agent: String,
+
+	// Pages fetched from the same host at the same time
+	concurrency: usize,
+
+	// Seconds between requests to the same host. A larger Crawl-delay in
+	// robots.txt takes precedence.
+	delay: f64,
+
+	// Times a page is fetched again after a 429 or 503 response
+	max_retries: u32,
+
+	obey_robots: bool,
+}
+
+impl Default for Limits {
+	fn default() -> Self {
+		Limits {
+			user_agent: concat!("mini_search_engine/", env!("CARGO_PKG_VERSION")).to_string(),
+			concurrency: 2,
+			delay: 0.5,
+			max_retries: 5,
+			obey_robots: true,
+		}
+	}
+}
+
+struct Options {
+	config_path: String,
+
+	// Number of pages fetched at the same time
+	threads: usize,
+
+	// Overrides the user agent of the domain
+	user_agent: Option<String>,
+
+	// Start over even if a crawl was interrupted or already complete, only
+	// fetching saved pages again if they changed
+	restart: bool,
+
+	extract_rules: ExtractRules,
+
+	// WARC file to append the responses of saved pages to
+	warc_path: Option<String>,
+
+	list_domains: bool,
+	domain: String,
+	output_dir: String,
+}
+
+struct Crawler {
+	whitelist: Vec<String>,
+	blacklist: Vec<String>,
+	output_dir: String,
+	start: String,
+	limits: Limits,
+	content_rules: ContentRules,
+	agent: ureq::Agent,
+	pages: Mutex<HashMap<String, CachedPage>>,
+	frontier: Mutex<Frontier>,
+	frontier_changed: Condvar,
+	warc: Option<Mutex<WarcWriter>>,
+}
+
+// URLs waiting to be fetched, and every URL seen so far so that no page is
+// fetched twice
+#[derive(Default)]
+struct Frontier {
+	// Hosts by origin, e.g. "https://www.php.net"
+	hosts: HashMap<String, Host>,
+	seen: HashSet<String>,
+
+	// Pages being fetched, which are saved as part of the frontier until they
+	// are done
+	fetching: HashSet<String>,
+
+	// Number of requests in progress, which can still add more URLs
+	active: usize,
+
+	// Sitemaps aren't saved in the frontier, since they are found again from
+	// robots.txt when a crawl is resumed
+	seen_sitemaps: HashSet<String>,
+
+	// Pages listed in sitemaps that are in the whitelist
+	sitemap_urls: HashSet<String>,
+
+	last_save: Option<Instant>,
+}
+
+struct Host {
+	queue: VecDeque<Request>,
+
+	// None until robots.txt has been fetched
+	robots: Option<Robots>,
+	fetching_robots: bool,
+
+	active: usize,
+	delay: Duration,
+	next_request: Instant,
+}
+
+struct Request {
+	url: String,
+	attempt: u32,
+	sitemap: bool,
+}
+
+enum Task {
+	Robots(String),
+	Page(String, Request),
+}
+
+enum Outcome {
+	Links(Vec<String>),
+
+	Sitemap(Sitemap),
+
+	// The server is overloaded or rate limiting, with the time it asked to
+	// wait if it did
+	Throttled(u16, Option<Duration>),
+}
+
+fn main() -> std::io::Result<()> {
+	let options = get_options();
+	let config: Config = serde_json::from_str(&std::fs::read_to_string(&options.config_path)?)?;
+	if options.list_domains {
+		let mut domains: Vec<_> = config.whitelists.keys().collect();
+		domains.sort_unstable();
+		for domain in domains {
+			println!("{}", domain);
+		}
+		return Ok(())
+	}
+
+	let Some(whitelist) = config.whitelists.get(&options.domain) else {
+		eprintln!("unsupported domain {}", options.domain);
+		std::process::exit(1);
+	};
+	let output_dir = format!("{}/{}", options.output_dir, options.domain);
+	let mut state = CrawlState::read(Path::new(&output_dir))?.unwrap_or_default();
+	if state.complete && !options.restart {
+		println!("{} was already crawled, use --restart to crawl it again", options.domain);
+		return Ok(())
+	}
+	let resume = state.start.is_some() && !state.complete && !options.restart;
+
+	let mut limits = config.limits.get(&options.domain).cloned().unwrap_or_default();
+	if let Some(user_agent) = options.user_agent {
+		limits.user_agent = user_agent;
+	}
+	let warc = match &options.warc_path {
+		Some(path) => {
+			let mut writer = WarcWriter::append(Path::new(path))?;
+			let info = format!("software: mini_search_engine/{}\r\nformat: WARC File Format 1.1\r\nhttp-header-user-agent: {}\r\n",
+				env!("CARGO_PKG_VERSION"), limits.user_agent);
+			writer.write(&warc::Record::new("warcinfo", "application/warc-fields", info.into_bytes()))?;
+			Some(Mutex::new(writer))
+		},
+		None => None,
+	};
+	let crawler = Crawler {
+		whitelist: whitelist.clone(),
+		blacklist: config.blacklists.get(&options.domain).cloned().unwrap_or_default(),
+		output_dir,
+		start: state.start.clone().filter(|_| resume).unwrap_or_else(|| chrono::Utc::now().to_rfc3339()),
+		agent: ureq::AgentBuilder::new().redirects(0).timeout(TIMEOUT).user_agent(&limits.user_agent).build(),
+		pages: Mutex::new(std::mem::take(&mut state.pages)),
+		limits,
+		content_rules: options.extract_rules.get(&options.domain),
+		frontier: Mutex::new(Frontier::default()),
+		frontier_changed: Condvar::new(),
+		warc,
+	};
+	{
+		let mut frontier = crawler.frontier.lock().unwrap();
+		if resume {
+			println!("Resuming crawl of {} with {} URLs left", options.domain, state.frontier.len());
+			frontier.seen.extend(state.visited);
+			crawler.add_urls(&mut frontier, state.frontier.into_iter());
+			frontier.sitemap_urls.extend(state.sitemap_urls);
+		} else {
+			crawler.add_urls(&mut frontier, whitelist.iter().cloned());
+		}
+		crawler.add_sitemaps(&mut frontier, config.sitemaps.get(&options.domain).cloned().unwrap_or_default());
+		crawler.save_state(&mut frontier, false)?;
+	}
+
+	std::thread::scope(|scope| {
+		for _ in 0..options.threads {
+			scope.spawn(|| crawler.run());
+		}
+	});
+	let mut frontier = crawler.frontier.lock().unwrap();
+	crawler.save_state(&mut frontier, true)
+}
+
+fn get_options() -> Options {
+	let mut args = std::env::args();
+	let program = args.next().unwrap_or("crawler".to_string());
+	let usage = || -> ! {
+		eprintln!("Usage: {} [--config FILE] [--threads N] [--user-agent AGENT] [--restart] [--extract-rules FILE] [--warc FILE] DOMAIN OUTPUT_DIR", program);
+		eprintln!("       {} [--config FILE] --list-domains", program);
+		std::process::exit(1);
+	};
+
+	let mut config_path = "spider/domains.json".to_string();
+	let mut extract_rules = ExtractRules::default();
+	let mut threads = 8;
+	let mut user_agent = None;
+	let mut restart = false;
+	let mut warc_path = None;
+	let mut list_domains = false;
+	let mut positional = Vec::new();
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--config" => config_path = args.next().unwrap_or_else(|| usage()),
+			"--threads" => {
+				threads = args.next().and_then(|n| n.parse().ok()).filter(|n| *n > 0).unwrap_or_else(|| usage());
+			},
+			"--user-agent" => user_agent = Some(args.next().unwrap_or_else(|| usage())),
+			"--restart" => restart = true,
+			"--extract-rules" => {
+				let path = args.next().unwrap_or_else(|| usage());
+				let text = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+					eprintln!("{}: {}", path, e);
+					std::process::exit(1);
+				});
+				extract_rules = ExtractRules::parse(&text).unwrap_or_else(|e| {
+					eprintln!("{}: {}", path, e);
+					std::process::exit(1);
+				});
+			},
+			"--warc" => warc_path = Some(args.next().unwrap_or_else(|| usage())),
+			"--list-domains" => list_domains = true,
+			_ => positional.push(arg),
+		}
+	}
+	if list_domains {
+		return Options {config_path, threads, user_agent, restart, extract_rules, warc_path, list_domains, domain: String::new(), output_dir: String::new()}
+	}
+	let [domain, output_dir] = <[String; 2]>::try_from(positional).unwrap_or_else(|_| usage());
+	Options {config_path, threads, user_agent, restart, extract_rules, warc_path, list_domains, domain, output_dir}
+}
+
+impl Crawler {
+	// Fetches pages until there are none left
+	fn run(&self) {
+		while let Some(task) = self.next_task() {
+			match task {
+				Task::Robots(origin) => {
+					let robots = self.get_robots(&origin);
+					let mut frontier = self.frontier.lock().unwrap();
+					let host = frontier.hosts.get_mut(&origin).unwrap();
+					if let Some(crawl_delay) = robots.crawl_delay {
+						host.delay = host.delay.max(Duration::from_secs_f64(crawl_delay.clamp(0.0, MAX_BACKOFF.as_secs_f64())));
+						host.next_request = Instant::now() + host.delay;
+					}
+					let sitemaps = robots.sitemaps.clone();
+					host.robots = Some(robots);
+					host.fetching_robots = false;
+					self.add_sitemaps(&mut frontier, sitemaps);
+					self.finish(&mut frontier, &origin);
+				},
+				Task::Page(origin, request) => {
+					let outcome = if request.sitemap { self.crawl_sitemap(&request.url) } else { self.crawl_page(&request.url) };
+					let mut frontier = self.frontier.lock().unwrap();
+					frontier.fetching.remove(&request.url);
+					match outcome {
+						Outcome::Links(links) => self.add_urls(&mut frontier, links.into_iter()),
+						Outcome::Sitemap(Sitemap::Pages(urls)) => {
+							let urls: Vec<String> = urls.into_iter().filter(|u| self.is_allowed(u)).collect();
+							frontier.sitemap_urls.extend(urls.iter().cloned());
+							self.add_urls(&mut frontier, urls.into_iter());
+						},
+						Outcome::Sitemap(Sitemap::Index(urls)) => self.add_sitemaps(&mut frontier, urls),
+						Outcome::Throttled(status, retry_after) => self.retry(&mut frontier, &origin, request, status, retry_after),
+					}
+					self.finish(&mut frontier, &origin);
+					if frontier.last_save.is_some_and(|t| t.elapsed() >= SAVE_INTERVAL) {
+						if let Err(e) = self.save_state(&mut frontier, false) {
+							eprintln!("Could not save crawl state: {}", e);
+						}
+					}
+				},
+			}
+		}
+	}
+
+	// Waits until a host is ready for another request, or returns None once
+	// every queue is empty and no other thread can add to them
+	fn next_task(&self) -> Option<Task> {
+		let mut frontier = self.frontier.lock().unwrap();
+		'search: loop {
+			let now = Instant::now();
+			let mut next_ready: Option<Instant> = None;
+			let Frontier {hosts, fetching, active, ..} = &mut *frontier;
+			for (origin, host) in hosts.iter_mut() {
+				if host.queue.is_empty() || host.active >= self.limits.concurrency || host.fetching_robots {
+					continue
+				}
+				if host.next_request > now {
+					next_ready = Some(next_ready.map_or(host.next_request, |t| t.min(host.next_request)));
+					continue
+				}
+
+				let task = match &host.robots {
+					None => {
+						host.fetching_robots = true;
+						Task::Robots(origin.clone())
+					},
+					Some(robots) => {
+						let request = host.queue.pop_front().unwrap();
+						if !request.sitemap && !robots.is_allowed(&get_path(&request.url)) {
+							println!("SKIP {}: DISALLOWED BY ROBOTS.TXT", request.url);
+							continue 'search
+						}
+						Task::Page(origin.clone(), request)
+					},
+				};
+				host.active += 1;
+				host.next_request = now + host.delay;
+				*active += 1;
+				if let Task::Page(_, request) = &task {
+					if !request.sitemap {
+						fetching.insert(request.url.clone());
+					}
+				}
+				return Some(task)
+			}
+
+			if frontier.active == 0 && frontier.hosts.values().all(|h| h.queue.is_empty()) {
+				return None
+			}
+			frontier = match next_ready {
+				Some(t) => self.frontier_changed.wait_timeout(frontier, t - now).unwrap().0,
+				None => self.frontier_changed.wait(frontier).unwrap(),
+			};
+		}
+	}
+
+	fn finish(&self, frontier: &mut Frontier, origin: &str) {
+		frontier.hosts.get_mut(origin).unwrap().active -= 1;
+		frontier.active -= 1;
+		self.frontier_changed.notify_all();
+	}
+
+	fn add_urls(&self, frontier: &mut Frontier, urls: impl Iterator<Item = String>) {
+		for mut url in urls {
+			// Fragments are the same page
+			if let Some(i) = url.find('#') {
+				url.truncate(i);
+			}
+			if !frontier.seen.insert(url.clone()) {
+				continue
+			}
+			if let Some(host) = self.get_host(frontier, &url) {
+				host.queue.push_back(Request {url, attempt: 0, sitemap: false});
+			}
+		}
+		self.frontier_changed.notify_all();
+	}
+
+	// Sitemaps are fetched before the pages waiting on the same host, so that
+	// the pages they list are found early
+	fn add_sitemaps(&self, frontier: &mut Frontier, urls: Vec<String>) {
+		for url in urls {
+			if !frontier.seen_sitemaps.insert(url.clone()) {
+				continue
+			}
+			if let Some(host) = self.get_host(frontier, &url) {
+				host.queue.push_front(Request {url, attempt: 0, sitemap: true});
+			}
+		}
+		self.frontier_changed.notify_all();
+	}
+
+	fn get_host<'a>(&self, frontier: &'a mut Frontier, url: &str) -> Option<&'a mut Host> {
+		let Ok(parsed_url) = Url::parse(url) else {
+			println!("SKIP {}: BAD URL", url);
+			return None
+		};
+		Some(frontier.hosts.entry(parsed_url.origin().ascii_serialization()).or_insert_with(|| Host {
+			queue: VecDeque::new(),
+			robots: (!self.limits.obey_robots).then(Robots::default),
+			fetching_robots: false,
+			active: 0,
+			delay: Duration::from_secs_f64(self.limits.delay.max(0.0)),
+			next_request: Instant::now(),
+		}))
+	}
+
+	// Saves the URLs that are left to fetch, including the ones being fetched
+	// now since they aren't done yet
+	fn save_state(&self, frontier: &mut Frontier, complete: bool) -> std::io::Result<()> {
+		let pending: HashSet<&String> = frontier.hosts.values()
+			.flat_map(|h| h.queue.iter().filter(|r| !r.sitemap).map(|r| &r.url))
+			.chain(&frontier.fetching)
+			.collect();
+		let state = CrawlState {
+			start: Some(self.start.clone()),
+			end: complete.then(|| chrono::Utc::now().to_rfc3339()),
+			complete,
+			frontier: pending.iter().map(|u| u.to_string()).collect(),
+			visited: frontier.seen.iter().filter(|u| !pending.contains(u)).cloned().collect(),
+			pages: self.pages.lock().unwrap().clone(),
+			sitemap_urls: frontier.sitemap_urls.iter().cloned().collect(),
+		};
+		state.write(Path::new(&self.output_dir))?;
+		frontier.last_save = Some(Instant::now());
+		Ok(())
+	}
+
+	// Tries the page again later, and makes every other request to the host
+	// wait as well
+	fn retry(&self, frontier: &mut Frontier, origin: &str, request: Request, status: u16, retry_after: Option<Duration>) {
+		if request.attempt >= self.limits.max_retries {
+			println!("SKIP {}: HTTP {}", request.url, status);
+			let links = self.get_saved_links(&request.url);
+			self.add_urls(frontier, links.into_iter());
+			return
+		}
+		let wait = retry_after.unwrap_or(INITIAL_BACKOFF * 2u32.saturating_pow(request.attempt)).min(MAX_BACKOFF);
+		println!("RETRY {} in {}s: HTTP {}", request.url, wait.as_secs(), status);
+		let host = frontier.hosts.get_mut(origin).unwrap();
+		host.next_request = host.next_request.max(Instant::now() + wait);
+		host.queue.push_front(Request {attempt: request.attempt + 1, ..request});
+	}
+
+	// A missing robots.txt allows everything, and so does one that can't be
+	// fetched, so that a broken server doesn't stop the crawl
+	fn get_robots(&self, origin: &str) -> Robots {
+		let url = format!("{}/robots.txt", origin);
+		let response = match self.agent.get(&url).call() {
+			Ok(response) if (200..300).contains(&response.status()) => response,
+			Ok(_) | Err(ureq::Error::Status(400..=499, _)) => return Robots::default(),
+			Err(e) => {
+				println!("SKIP {}: {}", url, e);
+				return Robots::default()
+			},
+		};
+		let mut bytes = Vec::new();
+		if let Err(e) = response.into_reader().take(MAX_PAGE_SIZE).read_to_end(&mut bytes) {
+			println!("SKIP {}: {}", url, e);
+			return Robots::default()
+		}
+		Robots::parse(&String::from_utf8_lossy(&bytes), &self.limits.user_agent)
+	}
+
+	// Fetches a page and saves it if it has enough content, then returns the
+	// URLs it links to. A page saved by an earlier crawl is only fetched again
+	// if it changed, and is kept if it can't be fetched for now.
+	fn crawl_page(&self, url: &str) -> Outcome {
+		if !self.is_allowed(url) {
+			println!("SKIP {}: URL NOT IN WHITELIST", url);
+			return Outcome::Links(Vec::new())
+		}
+
+		let cached = self.pages.lock().unwrap().get(url).cloned()
+			.filter(|c| !c.gone && Path::new(&self.get_page_path(url, "json")).exists());
+		let mut request = self.agent.get(url);
+		if let Some(cached) = &cached {
+			if let Some(etag) = &cached.etag {
+				request = request.set("If-None-Match", etag);
+			}
+			if let Some(last_modified) = &cached.last_modified {
+				request = request.set("If-Modified-Since", last_modified);
+			}
+		}
+
+		let response = match request.call() {
+			Ok(response) => response,
+			Err(ureq::Error::Status(status @ (429 | 503), response)) => {
+				return Outcome::Throttled(status, response.header("Retry-After").and_then(get_retry_after))
+			},
+			Err(ureq::Error::Status(status @ (404 | 410), response)) if cached.is_some() => {
+				let mut pages = self.pages.lock().unwrap();
+				let page = pages.get_mut(url).unwrap();
+				page.missing += 1;
+				if page.missing < MAX_MISSING {
+					println!("SKIP {}: HTTP {}, KEEPING SAVED COPY", url, status);
+					drop(pages);
+					return Outcome::Links(self.get_saved_links(url))
+				}
+				page.gone = true;
+				drop(pages);
+				println!("SKIP {}: HTTP {}, DELETING SAVED COPY", url, status);
+				// Archived so that the page is left out when indexing the archive
+				self.archive("response", url, &get_http_head(&response), &[]);
+				// Pages saved by older crawls have no HTML
+				for extension in ["json", "html"] {
+					match std::fs::remove_file(self.get_page_path(url, extension)) {
+						Err(e) if e.kind() != std::io::ErrorKind::NotFound => eprintln!("Could not delete {}: {}", url, e),
+						_ => {},
+					}
+				}
+				return Outcome::Links(Vec::new())
+			},
+			// Server errors and network errors are usually temporary
+			Err(ureq::Error::Status(status @ 500..=599, _)) => {
+				println!("SKIP {}: HTTP {}", url, status);
+				return Outcome::Links(self.get_saved_links(url))
+			},
+			Err(ureq::Error::Status(status, _)) => {
+				println!("SKIP {}: HTTP {}", url, status);
+				return Outcome::Links(Vec::new())
+			},
+			Err(e) => {
+				println!("SKIP {}: {}", url, e);
+				return Outcome::Links(self.get_saved_links(url))
+			},
+		};
+		if response.status() == 304 && cached.is_some() {
+			self.pages.lock().unwrap().get_mut(url).unwrap().missing = 0;
+			println!("{} (NOT MODIFIED)", url);
+			self.archive("revisit", url, &get_http_head(&response), &[]);
+			return Outcome::Links(self.get_saved_links(url))
+		}
+		// Redirects aren't followed, like in the spider
+		if !(200..300).contains(&response.status()) {
+			println!("SKIP {}: HTTP {}", url, response.status());
+			return Outcome::Links(Vec::new())
+		}
+
+		let content_type = response.header("Content-Type").unwrap_or("").to_string();
+		if !["text/html", "text/html;charset=utf-8"].contains(&content_type.to_lowercase().replace(' ', "").as_str()) {
+			println!("SKIP {}: BAD CHARSET \"{}\"", url, content_type);
+			return Outcome::Links(Vec::new())
+		}
+		let head = get_http_head(&response);
+		let page = CachedPage {
+			etag: response.header("ETag").map(|e| e.to_string()),
+			last_modified: response.header("Last-Modified").map(|l| l.to_string()),
+			missing: 0,
+			gone: false,
+		};
+
+		let mut bytes = Vec::new();
+		if let Err(e) = response.into_reader().take(MAX_PAGE_SIZE).read_to_end(&mut bytes) {
+			println!("SKIP {}: {}", url, e);
+			return Outcome::Links(self.get_saved_links(url))
+		}
+		let html = String::from_utf8_lossy(&bytes);
+
+		let Ok(parsed_url) = Url::parse(url) else {
+			println!("SKIP {}: BAD URL", url);
+			return Outcome::Links(Vec::new())
+		};
+		let mut webpage = extract::extract(&parsed_url, &html, &self.content_rules);
+		// The URL is kept as it was requested, not as the URL parser writes it
+		webpage.url = url.to_string();
+		webpage.links.retain(|l| self.is_allowed(l.url()));
+		let links: Vec<String> = webpage.links.iter().map(|l| l.url().to_string()).collect();
+
+		if webpage.text.chars().count() < extract::MIN_TEXT_LENGTH {
+			println!("SKIP {}: NOT ENOUGH CONTENT", url);
+			return Outcome::Links(Vec::new())
+		}
+		if let Err(e) = self.save(&webpage, &bytes) {
+			eprintln!("Could not save {}: {}", url, e);
+			return Outcome::Links(Vec::new())
+		}
+		self.pages.lock().unwrap().insert(url.to_string(), page);
+		self.archive("response", url, &head, &bytes);
+
+		println!("{}", url);
+		Outcome::Links(links)
+	}
+
+	// Returns the URLs listed in a sitemap, which are only used if they are in
+	// the whitelist
+	fn crawl_sitemap(&self, url: &str) -> Outcome {
+		let response = match self.agent.get(url).call() {
+			Ok(response) => response,
+			Err(ureq::Error::Status(status @ (429 | 503), response)) => {
+				return Outcome::Throttled(status, response.header("Retry-After").and_then(get_retry_after))
+			},
+			Err(e) => {
+				println!("SKIP {}: {}", url, e);
+				return Outcome::Links(Vec::new())
+			},
+		};
+		if !(200..300).contains(&response.status()) {
+			println!("SKIP {}: HTTP {}", url, response.status());
+			return Outcome::Links(Vec::new())
+		}
+		let mut bytes = Vec::new();
+		if let Err(e) = response.into_reader().take(MAX_SITEMAP_SIZE).read_to_end(&mut bytes) {
+			println!("SKIP {}: {}", url, e);
+			return Outcome::Links(Vec::new())
+		}
+		let sitemap = sitemap::parse(&String::from_utf8_lossy(&bytes));
+		match &sitemap {
+			Sitemap::Pages(urls) => println!("SITEMAP {} ({} pages)", url, urls.len()),
+			Sitemap::Index(urls) => println!("SITEMAP {} ({} sitemaps)", url, urls.len()),
+		}
+		Outcome::Sitemap(sitemap)
+	}
+
+	fn is_allowed(&self, url: &str) -> bool {
+		self.whitelist.iter().any(|w| url.starts_with(w.as_str()))
+			&& !self.blacklist.iter().any(|b| url.starts_with(b.as_str()))
+	}
+
+	// The HTML is saved as it was received, so that the page can be extracted
+	// again when the extraction rules change
+	fn save(&self, webpage: &Webpage, html: &[u8]) -> std::io::Result<()> {
+		std::fs::create_dir_all(&self.output_dir)?;
+		std::fs::write(self.get_page_path(&webpage.url, "html"), html)?;
+		std::fs::write(self.get_page_path(&webpage.url, "json"), serde_json::to_string(webpage)?)
+	}
+
+	// Appends a response or revisit record to the WARC file, if any. The body
+	// is archived as it was read, after ureq decompressed it.
+	fn archive(&self, warc_type: &str, url: &str, head: &str, body: &[u8]) {
+		let Some(warc) = &self.warc else {
+			return
+		};
+		let mut content = format!("{}Content-Length: {}\r\n\r\n", head, body.len()).into_bytes();
+		content.extend_from_slice(body);
+		let mut record = warc::Record::new(warc_type, "application/http;msgtype=response", content)
+			.with_header("WARC-Target-URI", url);
+		if warc_type == "revisit" {
+			record = record.with_header("WARC-Profile", "http://netpreserve.org/warc/1.1/revisit/server-not-modified")
+				.with_header("WARC-Refers-To-Target-URI", url);
+		}
+		if let Err(e) = warc.lock().unwrap().write(&record) {
+			eprintln!("Could not archive {}: {}", url, e);
+		}
+	}
+
+	// Same file layout as the spider, one file per page named after its URL
+	fn get_page_path(&self, url: &str, extension: &str) -> String {
+		format!("{}/{}.{}", self.output_dir, url.replace('/', "%2F"), extension)
+	}
+
+	// Links of the copy of a page saved by an earlier crawl, so that the crawl
+	// can go on past pages that weren't fetched this time
+	fn get_saved_links(&self, url: &str) -> Vec<String> {
+		let Ok(contents) = std::fs::read_to_string(self.get_page_path(url, "json")) else {
+			return Vec::new()
+		};
+		let Ok(webpage) = serde_json::from_str::<Webpage>(&contents) else {
+			return Vec::new()
+		};
+		webpage.links.iter().map(|l| l.url().to_string()).filter(|u| self.is_allowed(u)).collect()
+	}
+}
+
+// Status line and headers of a response, without the ones describing how the
+// body was sent
+fn get_http_head(response: &ureq::Response) -> String {
+	let mut head = format!("{} {} {}\r\n", response.http_version(), response.status(), response.status_text());
+	// Names are listed once for each value
+	let mut seen = HashSet::new();
+	for name in response.headers_names() {
+		if name == "content-length" || name == "transfer-encoding" || !seen.insert(name.clone()) {
+			continue
+		}
+		for value in response.all(&name) {
+			head.push_str(&format!("{}: {}\r\n", name, value));
+		}
+	}
+	head
+}
+
+// Robots.txt rules apply to the path and query string
+fn get_path(url: &str) -> String {
+	let Ok(url) = Url::parse(url) else {
+		return String::new()
+	};
+	match url.query() {
+		Some(query) => format!("{}?{}", url.path(), query),
+		None => url.path().to_string(),
+	}
+}
+
+// Retry-After is either a number of seconds or an HTTP date
+fn get_retry_after(value: &str) -> Option<Duration> {
+	if let Ok(seconds) = value.trim().parse::<u64>() {
+		return Some(Duration::from_secs(seconds))
+	}
+	let date = chrono::DateTime::parse_from_rfc2822(value.trim()).ok()?;
+	let seconds = (date.timestamp() - chrono::Utc::now().timestamp()).max(0);
+	Some(Duration::from_secs(seconds as u64))
+}
@@ -1,132 +1,1128 @@
-use std::fs::{File, ReadDir, read_dir, read_to_string};
-use std::io::{Write, Result};
-use std::path::PathBuf;
-use std::collections::{HashMap};
-use tantivy::{TantivyDocument, IndexWriter};
+use std::fs::{ReadDir, read_dir, read_to_string};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, mpsc};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Instant, SystemTime};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use tantivy::{TantivyDocument, IndexWriter, Term};
+use mini_search_engine::crawl_state::{self, CrawlState, CRAWL_STATE_FILE};
+use mini_search_engine::extract::{self, ContentRules, ExtractRules};
 use mini_search_engine::index;
+use mini_search_engine::jsonl;
+use mini_search_engine::fingerprint;
+use mini_search_engine::page_rank;
+use mini_search_engine::suggest::SuggestionsBuilder;
+use mini_search_engine::urls;
+use mini_search_engine::webpage::{Webpage, Section};
+use mini_search_engine::versions::{self, VersionRules};
+use mini_search_engine::compression::{self, Position};
+use mini_search_engine::warc::{self, HttpResponse};
 
-#[derive(serde::Deserialize)]
-struct Webpage {
-	links: Vec<String>,
-	title: String,
-	headings: String,
-	text: String,
-	code: String,
+// A document to index, either a whole page or one section of it
+struct PagePart<'a> {
+	url: String,
+	title: &'a str,
+
+	// Title of the page a section is from
+	parent_title: Option<&'a str>,
+
+	headings: &'a str,
+	text: Cow<'a, str>,
+	code: Cow<'a, str>,
+
+	// Headings within the part that search results can link to
+	sections: &'a [Section],
+}
+
+// Limit on the anchor text collected for each page, which is kept in memory
+// until the page is indexed
+const MAX_ANCHOR_TEXT_LENGTH: usize = 4096;
+
+// Limit on the headings stored for each page to link to from search results
+const MAX_SECTIONS: usize = 256;
+
+struct Page {
+	// Canonical URL of the page
+	url: String,
+
+	// Other URLs of the same page, including the URL it was crawled from if
+	// that wasn't canonical
+	aliases: Vec<String>,
+
+	// URL the page was crawled from, which links in its HTML are relative to
+	crawled_url: String,
+
+	source: PageSource,
+	size: u64,
+}
+
+enum PageSource {
+	// A JSON file written by the crawler, with the HTML of the page if it was
+	// saved
+	Files {path: PathBuf, html_path: Option<PathBuf>},
+
+	// A line of a JSON Lines file with all the pages of a domain
+	Jsonl {path: PathBuf, position: Position},
+
+	// A response record in a WARC file
+	Warc {path: PathBuf, position: Position},
 }
 
 struct Domain {
 	name: String,
-	pages: Vec<(String, Webpage)>,
-	page_ranks: Vec<f64>,
+	pages: Vec<Page>,
+	page_ranks: Vec<u64>,
+
+	// Ranks within the link graph of all domains, empty unless enabled
+	global_page_ranks: Vec<u64>,
+
+	// Text of the links pointing to each page, from any domain
+	anchors: Vec<String>,
+
+	// Documentation version of each page and the number of newer versions in
+	// the domain
+	versions: Vec<Option<(String, u64)>>,
+
+	// From the crawl state, or else the modification times of the oldest and
+	// newest page files
+	crawl_start: Option<SystemTime>,
+	crawl_end: Option<SystemTime>,
+
+	// Pages listed in the sitemaps of the domain, from the crawl state
+	sitemap_urls: Vec<String>,
+
+	// Rules to extract pages again from their HTML, if enabled, and always for
+	// pages from WARC files
+	content_rules: Option<ContentRules>,
+}
+
+// Pages listed in the sitemaps of a domain that aren't in the index, because
+// the crawler didn't save them or they were merged into another page
+#[derive(serde::Serialize)]
+struct Coverage {
+	domain: String,
+	sitemap_url_count: usize,
+	missing: Vec<String>,
+}
+
+// A page that was skipped because it couldn't be read or parsed, e.g. a
+// truncated file from an interrupted crawl
+#[derive(serde::Serialize)]
+struct PageError {
+	path: String,
+	error: String,
+
+	// Byte offset in the file where the error was found, if known
+	offset: Option<usize>,
+}
+
+struct ErrorReport {
+	// Stop at the first bad page instead of skipping it
+	strict: bool,
+
+	errors: Mutex<Vec<PageError>>,
+}
+
+struct Options {
+	analyzer_config: index::AnalyzerConfig,
+	strict: bool,
+
+	// Where to write a JSON list of pages with errors
+	report_path: Option<String>,
+
+	// Where to write a JSON list of sitemap URLs missing from the index
+	coverage_path: Option<String>,
+
+	// Number of threads reading and parsing pages
+	threads: usize,
+
+	// Memory used by tantivy to build segments before writing them to disk
+	memory_budget: usize,
+
+	page_rank: page_rank::PageRankOptions,
+	global_page_rank: bool,
+	version_rules: VersionRules,
+
+	// Domains whose pages are indexed as one document per section
+	split_sections: Vec<String>,
+
+	// Refuse to index domains whose crawl was interrupted, instead of only
+	// warning about them
+	require_complete: bool,
+
+	// Add to an existing index, only indexing documents that changed
+	update: bool,
+
+	// Extract pages again from their saved HTML with these rules, instead of
+	// reading the text extracted when they were crawled
+	reextract: bool,
+	extract_rules: ExtractRules,
+
+	input_path: String,
+	index_path: String,
 }
 
 fn main() -> tantivy::Result<()> {
-	let args: Vec<String> = std::env::args().collect();
-	if args.len() != 3 {
-		eprintln!("Usage: {} CRAWLER_OUTPUT INDEX", args.get(0).unwrap_or(&"create_index".to_string()));
-		std::process::exit(1);
+	let options = get_options()?;
+	let input_path = options.input_path.as_str();
+	let index_path = options.index_path.as_str();
+	let start_time = Instant::now();
+	let report = ErrorReport {strict: options.strict, errors: Mutex::new(Vec::new())};
+
+	// Domains are listed first, so that incomplete crawls are found before
+	// anything is written
+	let mut domains: Vec<Domain> = Vec::new();
+	let mut entries: Vec<PathBuf> = read_dir(input_path)?.map(|e| e.map(|e| e.path())).collect::<Result<_, _>>()?;
+	entries.sort_unstable();
+	for path in entries {
+		// A domain is either a directory with a file for each page, a JSON
+		// Lines file or a WARC file
+		if let Some(name) = compression::get_stem(&path, "warc").filter(|_| path.is_file()) {
+			let mut domain = get_warc_domain(name.to_string(), &path)?;
+			domain.content_rules = Some(options.extract_rules.get(&domain.name));
+			domains.push(domain);
+			continue
+		}
+		let jsonl_name = compression::get_stem(&path, "jsonl").filter(|_| path.is_file());
+		let (name, state) = match jsonl_name {
+			Some(name) => (name.to_string(), CrawlState::read_file(&crawl_state::get_domain_state_path(Path::new(input_path), name))?),
+			None if path.is_dir() => (get_path_name(path.clone()), CrawlState::read(&path)?),
+			None => continue,
+		};
+		if let Some(state) = state.as_ref().filter(|s| !s.complete) {
+			let message = format!("crawl of {} is incomplete ({} URLs left)", name, state.frontier.len());
+			if options.require_complete {
+				return Err(tantivy::TantivyError::InvalidArgument(message))
+			}
+			println!("Warning: {}", message);
+		}
+		let mut domain = match jsonl_name {
+			Some(_) => get_jsonl_domain(name, &path, &report)?,
+			None => get_domain(name, read_dir(path)?)?,
+		};
+		if let Some(state) = state {
+			domain.crawl_start = state.start.as_deref().and_then(parse_time).or(domain.crawl_start);
+			domain.crawl_end = state.end.as_deref().and_then(parse_time).or(domain.crawl_end);
+			domain.sitemap_urls = state.sitemap_urls;
+		}
+		if options.reextract {
+			domain.content_rules = Some(options.extract_rules.get(&domain.name));
+		}
+		domains.push(domain);
+	}
+	let mut names = HashSet::new();
+	if let Some(domain) = domains.iter().find(|d| !names.insert(d.name.as_str())) {
+		let message = format!("{} is found more than once in {}", domain.name, input_path);
+		return Err(tantivy::TantivyError::InvalidArgument(message))
 	}
-	let input_path = args[1].as_str();
-	let index_path = args[2].as_str();
 
 	let schema = index::get_schema();
-	let index = index::create_index(&schema, index_path)?;
-	let mut writer: IndexWriter = index.writer(512 * 1024 * 1024)?;
-	let mut domain_list = File::create(index_path.to_string() + "/domains.txt")?;
+	let mut manifest = index::IndexManifest::new(input_path, options.analyzer_config.clone());
+	manifest.page_rank = options.page_rank.clone();
+	manifest.global_page_rank = options.global_page_rank;
+	manifest.split_sections = options.split_sections.clone();
+	// When updating, documents are kept if their content hash is the same
+	let updating = options.update && Path::new(index_path).join(index::MANIFEST_FILE).exists();
+	let (index, indexed_hashes) = if updating {
+		manifest.analyzer = index::IndexManifest::open(index_path)?.analyzer;
+		let index = index::open_index(index_path)?;
+		let indexed_hashes = get_content_hashes(&index)?;
+		println!("Updating {} documents...", indexed_hashes.len());
+		(index, indexed_hashes)
+	} else {
+		(index::create_index(&schema, index_path, &manifest)?, HashMap::new())
+	};
+	let mut writer: IndexWriter = index.writer(options.memory_budget)?;
+	let current_urls = Mutex::new(HashSet::new());
+	let unchanged_count = AtomicUsize::new(0);
+	let suggestions = Mutex::new(SuggestionsBuilder::default());
+	let mut page_count = 0;
+	let mut byte_count = 0;
 
-	for entry in read_dir(input_path)? {
-		let path = entry?.path();
-		if !path.is_dir() {
-			continue
-		}
-		let mut name = get_path_name(path.clone());
+	println!("Reading links...");
+	read_links(&mut domains, &options, &report)?;
+	for domain in &mut domains {
+		domain.versions = get_versions(&domain.pages, &options.version_rules);
+	}
+	let coverage: Vec<Coverage> = domains.iter().filter(|d| !d.sitemap_urls.is_empty()).map(get_coverage).collect();
+
+	for domain in &domains {
+		println!("Indexing {}...", domain.name);
+
+		// Second pass: read each page again, this time in full, and send it
+		// straight to the index writer
+		let page_indices: Vec<usize> = (0..domain.pages.len()).collect();
+		let indexed = map_parallel(&page_indices, options.threads, |i| {
+			let page = &domain.pages[*i];
+			let Some(webpage) = report.check(read_webpage(page, domain.content_rules.as_ref()))? else {
+				return Ok(None)
+			};
 
-		println!("Indexing {}...", name);
-		let domain = get_domain(name.clone(), read_dir(path)?)?;
+			let page_rank = domain.page_ranks[*i];
+			let split = options.split_sections.contains(&domain.name);
+			for (j, part) in get_page_parts(page, &webpage, split).into_iter().enumerate() {
+				suggestions.lock().unwrap().add(part.title, page_rank);
 
-		name.push('\n');
-		domain_list.write_all(name.as_bytes())?;
+				// Links and other URLs of the page lead to its first part
+				let (aliases, anchors): (&[String], &str) = if j == 0 {
+					(&page.aliases, &domain.anchors[*i])
+				} else {
+					(&[], "")
+				};
+				let content_hash = get_document_hash(&domain.name, &part, aliases, anchors, &domain.versions[*i]);
+				if updating {
+					current_urls.lock().unwrap().insert(part.url.clone());
+					if indexed_hashes.get(&part.url) == Some(&content_hash) {
+						unchanged_count.fetch_add(1, Ordering::Relaxed);
+						continue
+					}
+					writer.delete_term(Term::from_field_text(schema.url, &part.url));
+				}
+
+				let mut document = TantivyDocument::new();
+				document.add_text(schema.domain, domain.name.as_str());
+				document.add_text(schema.url, part.url.as_str());
+				document.add_u64(schema.page_rank, page_rank);
+				if let Some(global_page_rank) = domain.global_page_ranks.get(*i) {
+					document.add_u64(schema.global_page_rank, *global_page_rank);
+				}
+				document.add_u64(schema.fingerprint, fingerprint::get_fingerprint(&part.text));
+				document.add_u64(schema.content_hash, content_hash);
+				if let Some((version, age)) = &domain.versions[*i] {
+					document.add_text(schema.version, version.as_str());
+					document.add_u64(schema.version_age, *age);
+				}
+				document.add_text(schema.title, part.title);
+				if let Some(parent_title) = part.parent_title {
+					document.add_text(schema.parent_title, parent_title);
+				}
+				document.add_text(schema.headings, part.title);
+				document.add_text(schema.headings, part.headings);
+				document.add_text(schema.text, &part.text);
+				document.add_text(schema.code, &part.code);
+				for section in part.sections.iter().filter(|s| !s.id.is_empty()).take(MAX_SECTIONS) {
+					let heading = section.heading.replace('\t', " ");
+					document.add_text(schema.sections, format!("{}\t{}\t{}", section.level, section.id, heading));
+				}
+
+				for alias in aliases {
+					document.add_text(schema.aliases, alias.as_str());
+				}
+				if j == 0 {
+					document.add_text(schema.anchors, anchors);
+				}
+				writer.add_document(document)?;
+			}
+			Ok(Some(page.size))
+		})?;
+		let indexed: Vec<u64> = indexed.into_iter().flatten().collect();
+
+		manifest.domains.push(index::DomainManifest {
+			name: domain.name.clone(),
+			page_count: indexed.len() as u64,
+			crawl_start: domain.crawl_start.map(format_time),
+			crawl_end: domain.crawl_end.map(format_time),
+		});
+
+		page_count += indexed.len();
+		byte_count += indexed.iter().sum::<u64>();
+	}
 
-		for (i, (url, webpage)) in domain.pages.iter().enumerate() {
-			let mut document = TantivyDocument::new();
-			document.add_text(schema.domain, domain.name.as_str());
-			document.add_text(schema.url, url.as_str());
-			document.add_u64(schema.page_rank, (domain.page_ranks[i] * std::u64::MAX as f64) as u64);
-			document.add_text(schema.title, webpage.title.as_str());
-			document.add_text(schema.headings, webpage.title.as_str());
-			document.add_text(schema.headings, webpage.headings.as_str());
-			document.add_text(schema.text, webpage.text.as_str());
-			document.add_text(schema.code, webpage.code.as_str());
-			writer.add_document(document)?;
+	if updating {
+		// Documents of pages that are gone, or of sections that were merged
+		let current_urls = current_urls.into_inner().unwrap();
+		let removed: Vec<&String> = indexed_hashes.keys().filter(|u| !current_urls.contains(*u)).collect();
+		for url in &removed {
+			writer.delete_term(Term::from_field_text(schema.url, url));
 		}
+		println!("Kept {} unchanged documents, removed {} documents", unchanged_count.into_inner(), removed.len());
 	}
 
 	println!("Committing...");
 	writer.commit()?;
 
+	manifest.write(index_path)?;
+
+	println!("Writing suggestions...");
+	let suggestions = suggestions.into_inner().unwrap();
+	suggestions.write(index_path).map_err(|e| tantivy::TantivyError::SystemError(e.to_string()))?;
+
+	let seconds = start_time.elapsed().as_secs_f64();
+	let mebibytes = byte_count as f64 * 2f64.powf(-20f64);
+	println!("Indexed {} pages ({:.1} MiB) in {:.1} seconds ({:.1} pages/s, {:.1} MiB/s)",
+		page_count, mebibytes, seconds, page_count as f64 / seconds, mebibytes / seconds);
+
+	report.print_summary();
+	if let Some(report_path) = options.report_path {
+		report.write(&report_path)?;
+	}
+
+	if !coverage.is_empty() {
+		println!("Sitemap coverage:");
+		for c in &coverage {
+			println!("  {}: {} of {} sitemap URLs indexed", c.domain, c.sitemap_url_count - c.missing.len(), c.sitemap_url_count);
+		}
+	}
+	if let Some(coverage_path) = options.coverage_path {
+		std::fs::write(coverage_path, serde_json::to_string_pretty(&coverage)?)?;
+	}
+
 	println!("Done");
 	Ok(())
 }
 
-fn get_domain(name: String, dir: ReadDir) -> Result<Domain> {
+impl Page {
+	fn add_alias(&mut self, url: String) {
+		if url != self.url && !self.aliases.contains(&url) {
+			self.aliases.push(url);
+		}
+	}
+}
+
+impl ErrorReport {
+	// Returns None if the page should be skipped
+	fn check<R>(&self, result: Result<R, PageError>) -> tantivy::Result<Option<R>> {
+		match result {
+			Ok(r) => Ok(Some(r)),
+			Err(e) if self.strict => Err(tantivy::TantivyError::InvalidArgument(e.to_string())),
+			Err(e) => {
+				self.errors.lock().unwrap().push(e);
+				Ok(None)
+			},
+		}
+	}
+
+	fn print_summary(&self) {
+		// Only the first few are printed, the report has the full list
+		let errors = self.errors.lock().unwrap();
+		if errors.is_empty() {
+			return
+		}
+		println!("Skipped {} pages with errors:", errors.len());
+		for e in errors.iter().take(10) {
+			println!("  {}", e);
+		}
+		if errors.len() > 10 {
+			println!("  ...");
+		}
+	}
+
+	fn write(&self, path: &str) -> std::io::Result<()> {
+		let errors = self.errors.lock().unwrap();
+		std::fs::write(path, serde_json::to_string_pretty(&*errors)?)
+	}
+}
+
+impl std::fmt::Display for PageError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self.offset {
+			Some(offset) => write!(f, "{}: {} (at byte {})", self.path, self.error, offset),
+			None => write!(f, "{}: {}", self.path, self.error),
+		}
+	}
+}
+
+// Pages crawled before their HTML was saved are always read as extracted by
+// the crawler
+fn read_webpage(page: &Page, content_rules: Option<&ContentRules>) -> Result<Webpage, PageError> {
+	let (path, html_path) = match &page.source {
+		PageSource::Files {path, html_path} => (path, html_path),
+		PageSource::Jsonl {path, position} => return read_page_line(page, path, *position, content_rules),
+		PageSource::Warc {path, position} => return read_archived_page(page, path, *position, content_rules),
+	};
+	let (Some(content_rules), Some(html_path)) = (content_rules, html_path) else {
+		return read_page(path)
+	};
+	let page_error = |error: String| PageError {
+		path: html_path.to_string_lossy().to_string(),
+		error,
+		offset: None,
+	};
+	let bytes = std::fs::read(html_path).map_err(|e| page_error(e.to_string()))?;
+	let url = url::Url::parse(&page.crawled_url).map_err(|e| page_error(e.to_string()))?;
+	Ok(extract::extract(&url, &String::from_utf8_lossy(&bytes), content_rules))
+}
+
+fn read_page_line(page: &Page, path: &Path, position: Position, content_rules: Option<&ContentRules>) -> Result<Webpage, PageError> {
+	let page_error = |error: String| PageError {
+		path: path.to_string_lossy().to_string(),
+		error: format!("{}: {}", page.crawled_url, error),
+		offset: Some((position.frame + position.offset) as usize),
+	};
+	let line = jsonl::read_page_at(path, position).map_err(|e| page_error(e.to_string()))?;
+	let (Some(content_rules), Some(html)) = (content_rules, line.html) else {
+		return Ok(line.webpage)
+	};
+	let url = url::Url::parse(&page.crawled_url).map_err(|e| page_error(e.to_string()))?;
+	let mut webpage = extract::extract(&url, &html, content_rules);
+	webpage.url = page.crawled_url.clone();
+	Ok(webpage)
+}
+
+fn read_archived_page(page: &Page, path: &Path, position: Position, content_rules: Option<&ContentRules>) -> Result<Webpage, PageError> {
+	let page_error = |error: String| PageError {
+		path: path.to_string_lossy().to_string(),
+		error: format!("{}: {}", page.crawled_url, error),
+		offset: Some((position.frame + position.offset) as usize),
+	};
+	let record = warc::read_record_at(path, position).map_err(|e| page_error(e.to_string()))?;
+	let response = HttpResponse::parse(&record.content).ok_or_else(|| page_error("not an HTTP response".to_string()))?;
+	let bytes = response.get_body().map_err(|e| page_error(e.to_string()))?;
+	let url = url::Url::parse(&page.crawled_url).map_err(|e| page_error(e.to_string()))?;
+	let default_rules = ContentRules::default();
+	let mut webpage = extract::extract(&url, &String::from_utf8_lossy(&bytes), content_rules.unwrap_or(&default_rules));
+	webpage.url = page.crawled_url.clone();
+	Ok(webpage)
+}
+
+fn read_page<T: DeserializeOwned>(path: &Path) -> Result<T, PageError> {
+	let page_error = |error: String, offset| PageError {
+		path: path.to_string_lossy().to_string(),
+		error,
+		offset,
+	};
+	let bytes = std::fs::read(path).map_err(|e| page_error(e.to_string(), None))?;
+	let contents = std::str::from_utf8(&bytes).map_err(|e| page_error(e.to_string(), Some(e.valid_up_to())))?;
+	serde_json::from_str(contents).map_err(|e| {
+		let offset = get_byte_offset(contents, e.line(), e.column());
+		page_error(e.to_string(), offset)
+	})
+}
+
+// Converts the one-based line and column from serde_json to a byte offset
+fn get_byte_offset(contents: &str, line: usize, column: usize) -> Option<usize> {
+	if line == 0 {
+		return None
+	}
+	let line_start: usize = contents.split_inclusive('\n').take(line - 1).map(|l| l.len()).sum();
+	Some(line_start + column.saturating_sub(1))
+}
+
+fn get_options() -> tantivy::Result<Options> {
+	let mut args = std::env::args();
+	let program = args.next().unwrap_or("indexer".to_string());
+	let usage = || -> ! {
+		eprintln!("Usage: {} [--analyzer CONFIG] [--strict] [--report FILE] [--coverage FILE] [--threads N] [--memory MIB] [--global-page-rank] [--damping D] [--page-rank-iterations N] [--page-rank-tolerance T] [--versions FILE] [--split-sections DOMAIN]... [--require-complete] [--update] [--reextract] [--extract-rules FILE] CRAWLER_OUTPUT INDEX", program);
+		std::process::exit(1);
+	};
+
+	let mut analyzer_config = index::AnalyzerConfig::default();
+	let mut strict = false;
+	let mut report_path = None;
+	let mut coverage_path = None;
+	let mut threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+	let mut memory_budget = 512 * 1024 * 1024;
+	let mut page_rank = page_rank::PageRankOptions::default();
+	let mut global_page_rank = false;
+	let mut version_rules = VersionRules::default();
+	let mut split_sections = Vec::new();
+	let mut require_complete = false;
+	let mut update = false;
+	let mut reextract = false;
+	let mut extract_rules = ExtractRules::default();
+	let mut paths = Vec::new();
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--analyzer" => {
+				let path = args.next().unwrap_or_else(|| usage());
+				analyzer_config = read_analyzer_config(&path)?;
+			},
+			"--strict" => strict = true,
+			"--report" => report_path = Some(args.next().unwrap_or_else(|| usage())),
+			"--coverage" => coverage_path = Some(args.next().unwrap_or_else(|| usage())),
+			"--threads" => {
+				threads = args.next().and_then(|n| n.parse().ok()).filter(|n| *n > 0).unwrap_or_else(|| usage());
+			},
+			"--memory" => {
+				let mebibytes: usize = args.next().and_then(|n| n.parse().ok()).unwrap_or_else(|| usage());
+				memory_budget = mebibytes * 1024 * 1024;
+			},
+			"--global-page-rank" => global_page_rank = true,
+			"--split-sections" => split_sections.push(args.next().unwrap_or_else(|| usage())),
+			"--require-complete" => require_complete = true,
+			"--update" => update = true,
+			"--reextract" => reextract = true,
+			"--extract-rules" => {
+				let path = args.next().unwrap_or_else(|| usage());
+				extract_rules = ExtractRules::parse(&read_to_string(&path)?)
+					.map_err(|e| tantivy::TantivyError::InvalidArgument(format!("{}: {}", path, e)))?;
+			},
+			"--versions" => {
+				let path = args.next().unwrap_or_else(|| usage());
+				version_rules = VersionRules::parse(&read_to_string(path)?)
+					.map_err(|e| tantivy::TantivyError::InvalidArgument(e.to_string()))?;
+			},
+			"--damping" => {
+				page_rank.damping = args.next().and_then(|d| d.parse().ok()).filter(|d| (0.0..1.0).contains(d)).unwrap_or_else(|| usage());
+			},
+			"--page-rank-iterations" => {
+				page_rank.max_iterations = args.next().and_then(|n| n.parse().ok()).unwrap_or_else(|| usage());
+			},
+			"--page-rank-tolerance" => {
+				page_rank.tolerance = args.next().and_then(|t| t.parse().ok()).unwrap_or_else(|| usage());
+			},
+			_ => paths.push(arg),
+		}
+	}
+	let [input_path, index_path] = <[String; 2]>::try_from(paths).unwrap_or_else(|_| usage());
+	Ok(Options {analyzer_config, strict, report_path, coverage_path, threads, memory_budget, page_rank, global_page_rank, version_rules, split_sections, require_complete, update, reextract, extract_rules, input_path, index_path})
+}
+
+fn read_analyzer_config(path: &str) -> std::io::Result<index::AnalyzerConfig> {
+	let contents = read_to_string(path)?;
+	Ok(serde_json::from_str(&contents)?)
+}
+
+// Lists the pages of a domain, without reading them yet
+fn get_domain(name: String, dir: ReadDir) -> tantivy::Result<Domain> {
 	let mut pages = Vec::new();
+	let mut crawl_start: Option<SystemTime> = None;
+	let mut crawl_end: Option<SystemTime> = None;
 	for entry in dir {
 		let path = entry?.path();
-		if !path.is_file() {
+		if !path.is_file() || path.ends_with(CRAWL_STATE_FILE) {
 			continue
 		}
 
-		let mut url = get_path_name(path.clone()).replace("%2F", "/");
-		if !url.ends_with(".json") {
+		let mut crawled_url = get_path_name(path.clone()).replace("%2F", "/");
+		if !crawled_url.ends_with(".json") {
 			continue
 		}
-		url.truncate(url.len() - 5);
+		crawled_url.truncate(crawled_url.len() - 5);
+
+		let metadata = path.metadata()?;
+		let html_path = path.with_file_name(format!("{}.html", crawled_url.replace('/', "%2F")));
+		let html_path = html_path.is_file().then_some(html_path);
+		if let Ok(modified) = metadata.modified() {
+			crawl_start = Some(crawl_start.map_or(modified, |t| t.min(modified)));
+			crawl_end = Some(crawl_end.map_or(modified, |t| t.max(modified)));
+		}
+
+		let source = PageSource::Files {path, html_path};
+		let mut page = Page {url: urls::canonicalize(&crawled_url), aliases: Vec::new(), crawled_url: crawled_url.clone(), source, size: metadata.len()};
+		page.add_alias(crawled_url);
+		pages.push(page);
+	}
+	sort_pages(&mut pages);
+	Ok(Domain {name, pages, page_ranks: Vec::new(), global_page_ranks: Vec::new(), anchors: Vec::new(), versions: Vec::new(), crawl_start, crawl_end, sitemap_urls: Vec::new(), content_rules: None})
+}
+
+// Lists the pages of a JSON Lines file. A URL found on more than one line
+// keeps the last one.
+fn get_jsonl_domain(name: String, path: &Path, report: &ErrorReport) -> tantivy::Result<Domain> {
+	let mut lines: HashMap<String, (Position, u64)> = HashMap::new();
+	let mut errors = Vec::new();
+	jsonl::read_urls(path, |position, size, url| {
+		match url {
+			Ok(url) => {
+				lines.insert(url, (position, size));
+			},
+			Err(e) => errors.push(PageError {
+				path: path.to_string_lossy().to_string(),
+				error: e.to_string(),
+				offset: Some((position.frame + position.offset) as usize),
+			}),
+		}
+		Ok(())
+	})?;
+	for error in errors {
+		report.check::<()>(Err(error))?;
+	}
+
+	let mut pages = Vec::new();
+	for (crawled_url, (position, size)) in lines {
+		let source = PageSource::Jsonl {path: path.to_path_buf(), position};
+		let mut page = Page {url: urls::canonicalize(&crawled_url), aliases: Vec::new(), crawled_url: crawled_url.clone(), source, size};
+		page.add_alias(crawled_url);
+		pages.push(page);
+	}
+	sort_pages(&mut pages);
+
+	// Lines don't say when they were crawled
+	let modified = path.metadata()?.modified().ok();
+	Ok(Domain {name, pages, page_ranks: Vec::new(), global_page_ranks: Vec::new(), anchors: Vec::new(), versions: Vec::new(), crawl_start: modified, crawl_end: modified, sitemap_urls: Vec::new(), content_rules: None})
+}
+
+// Lists the HTML pages archived in a WARC file. When a URL was archived more
+// than once, e.g. by several crawls appended to the same file, the last
+// response is used, and a page is left out if its last response was an error.
+fn get_warc_domain(name: String, path: &Path) -> tantivy::Result<Domain> {
+	let mut responses: HashMap<String, Option<(Position, u64)>> = HashMap::new();
+	let mut crawl_start: Option<SystemTime> = None;
+	let mut crawl_end: Option<SystemTime> = None;
+	warc::read_records(path, |position, record| {
+		// Revisit records only say that the page didn't change
+		let (Some("response"), Some(url)) = (record.header("WARC-Type"), record.header("WARC-Target-URI")) else {
+			return Ok(())
+		};
+		if !url.starts_with("http://") && !url.starts_with("https://") {
+			return Ok(())
+		}
+		let Some(response) = HttpResponse::parse(&record.content) else {
+			return Ok(())
+		};
+		let page = ((200..300).contains(&response.status) && response.is_html())
+			.then_some((position, record.content.len() as u64));
+		responses.insert(url.to_string(), page);
+		if let Some(date) = record.header("WARC-Date").and_then(parse_time) {
+			crawl_start = Some(crawl_start.map_or(date, |t| t.min(date)));
+			crawl_end = Some(crawl_end.map_or(date, |t| t.max(date)));
+		}
+		Ok(())
+	}).map_err(|e| tantivy::TantivyError::InvalidArgument(format!("{}: {}", path.display(), e)))?;
 
-		let contents = read_to_string(&path)?;
-		pages.push((url, serde_json::from_str(&contents)?));
+	let mut pages = Vec::new();
+	for (crawled_url, response) in responses {
+		let Some((position, size)) = response else {
+			continue
+		};
+		let source = PageSource::Warc {path: path.to_path_buf(), position};
+		let mut page = Page {url: urls::canonicalize(&crawled_url), aliases: Vec::new(), crawled_url: crawled_url.clone(), source, size};
+		page.add_alias(crawled_url);
+		pages.push(page);
 	}
-	let page_ranks = get_page_ranks(&pages);
-	Ok(Domain {name, pages, page_ranks})
+	sort_pages(&mut pages);
+	Ok(Domain {name, pages, page_ranks: Vec::new(), global_page_ranks: Vec::new(), anchors: Vec::new(), versions: Vec::new(), crawl_start, crawl_end, sitemap_urls: Vec::new(), content_rules: None})
+}
+
+// Shortest URLs first, since they are kept when pages are merged, and https
+// before http for the same page. Pages with the same canonical URL are ordered
+// by the URL they were crawled from, so that the same pages always give the
+// same index.
+fn sort_pages(pages: &mut [Page]) {
+	pages.sort_by_cached_key(|p| {
+		let key = urls::get_key(&p.url);
+		(key.len(), key, !p.url.starts_with("https://"), p.crawled_url.clone())
+	});
 }
 
-fn get_page_ranks(pages: &Vec<(String, Webpage)>) -> Vec<f64> {
+// First pass over all domains, which only reads the links of each page to
+// compute page ranks and collect anchor text
+fn read_links(domains: &mut [Domain], options: &Options, report: &ErrorReport) -> tantivy::Result<()> {
+	// Pages are numbered across all domains so that links between domains can
+	// be followed too
 	let mut page_indices = HashMap::new();
-	for (i, (url, _)) in pages.iter().enumerate() {
-		page_indices.insert(url, i);
-	}
-
-	let mut page_ranks = Vec::new();
-	let mut outbound = Vec::new();
-	let mut graph = vec![Vec::new(); pages.len()];
-
-	let init = 1.0 / pages.len() as f64;
-	for (i, (_, page)) in pages.iter().enumerate() {
-		page_ranks.push(init);
-		outbound.push(1.0 / (page.links.len() + 1) as f64);
-		graph[i].push(i);
-		for link in &page.links {
-			if let Some(ind) = page_indices.get(link) {
-				graph[*ind].push(i);
+	for (i, page) in domains.iter().flat_map(|d| &d.pages).enumerate() {
+		page_indices.entry(urls::get_key(&page.url)).or_insert(i);
+	}
+	let pages: Vec<(&Page, Option<&ContentRules>)> = domains.iter()
+		.flat_map(|d| d.pages.iter().map(|p| (p, d.content_rules.as_ref())))
+		.collect();
+	// Anchor text is added to the pages links point to as soon as each page
+	// has been read, so that only the capped text of each page is kept
+	let mut page_anchors = vec![String::new(); pages.len()];
+	let mut links = Vec::with_capacity(pages.len());
+	for_each_parallel(&pages, options.threads, |(page, content_rules)| {
+		// Only the links are kept, the rest of the page is just hashed to find
+		// duplicates
+		let Some(webpage) = report.check(read_webpage(page, *content_rules))? else {
+			return Ok(None)
+		};
+		// Archives can have pages the crawler wouldn't have saved
+		if matches!(page.source, PageSource::Warc {..}) && webpage.text.chars().count() < extract::MIN_TEXT_LENGTH {
+			return Ok(None)
+		}
+		// Links to the page itself, or to a page with the same URL, don't
+		// describe it
+		let own_index = page_indices.get(&urls::get_key(&page.url));
+		let targets: Vec<(usize, String)> = webpage.links.iter().filter_map(|l| {
+			let t = page_indices.get(&urls::get_key(l.url()))?;
+			Some((*t, l.text().to_string()))
+		}).filter(|(t, _)| Some(t) != own_index).collect();
+		Ok(Some((get_content_hash(&webpage), targets)))
+	}, |result| {
+		links.push(result.map(|(hash, targets)| {
+			let targets: Vec<usize> = targets.into_iter().map(|(t, text)| {
+				add_anchor_text(&mut page_anchors[t], &text);
+				t
+			}).collect();
+			(hash, targets)
+		}));
+	})?;
+	drop(page_indices);
+	drop(pages);
+
+	// Pages with the same URL or content as an earlier page of the same domain
+	// are merged into it. Pages are sorted by URL length, so the shortest URL
+	// is kept.
+	let mut canonical_pages: Vec<Option<usize>> = vec![None; links.len()];
+	let mut start = 0;
+	for domain in domains.iter() {
+		let end = start + domain.pages.len();
+		let mut by_url = HashMap::new();
+		let mut by_hash = HashMap::new();
+		for i in start..end {
+			let Some((hash, _)) = &links[i] else {
+				continue
+			};
+			let url = urls::get_key(&domain.pages[i - start].url);
+			let canonical = by_url.get(&url).or(by_hash.get(hash)).copied().unwrap_or(i);
+			by_url.entry(url).or_insert(canonical);
+			by_hash.entry(*hash).or_insert(canonical);
+			canonical_pages[i] = Some(canonical);
+		}
+		start = end;
+	}
+
+	// Leave out pages that couldn't be read, along with links to them, and
+	// point links to merged pages at the page they were merged into
+	let mut new_indices = vec![None; links.len()];
+	let mut kept = 0;
+	for (i, canonical) in canonical_pages.iter().enumerate() {
+		match canonical {
+			Some(c) if *c == i => {
+				new_indices[i] = Some(kept);
+				kept += 1;
+			},
+			Some(c) => new_indices[i] = new_indices[*c],
+			None => {},
+		}
+	}
+	let mut start = 0;
+	let mut duplicate_count = 0;
+	for domain in domains.iter_mut() {
+		let mut pages: Vec<Option<Page>> = std::mem::take(&mut domain.pages).into_iter().map(Some).collect();
+		for j in 0..pages.len() {
+			match canonical_pages[start + j] {
+				Some(c) if c != start + j => {
+					let duplicate = pages[j].take().unwrap();
+					let page = pages[c - start].as_mut().unwrap();
+					for alias in std::iter::once(duplicate.url).chain(duplicate.aliases) {
+						page.add_alias(alias);
+					}
+					duplicate_count += 1;
+				},
+				Some(_) => {},
+				None => pages[j] = None,
+			}
+		}
+		start += pages.len();
+		domain.pages = pages.into_iter().flatten().collect();
+	}
+	if duplicate_count > 0 {
+		println!("Merged {} duplicate pages", duplicate_count);
+	}
+
+	// Merged pages get the anchor text of all the pages merged into them
+	let mut anchors = vec![String::new(); kept];
+	for (i, text) in page_anchors.into_iter().enumerate() {
+		if let Some(t) = new_indices[i] {
+			for line in text.lines() {
+				add_anchor_text(&mut anchors[t], line);
 			}
 		}
 	}
+	let links: Vec<Vec<usize>> = links.into_iter().enumerate()
+		.filter(|(i, _)| canonical_pages[*i] == Some(*i))
+		.map(|(i, l)| {
+			let i = new_indices[i].unwrap();
+			let (_, targets) = l.unwrap();
+			targets.into_iter().filter_map(|t| new_indices[t]).filter(|t| *t != i).collect()
+		}).collect();
+
+	let global_page_ranks = if options.global_page_rank {
+		println!("Computing global page ranks...");
+		page_rank::scale_page_ranks(&page_rank::get_page_ranks(&links, &options.page_rank))
+	} else {
+		Vec::new()
+	};
+
+	// Local page ranks only follow links within the same domain
+	let mut start = 0;
+	for domain in domains.iter_mut() {
+		let end = start + domain.pages.len();
+		let local_links: Vec<Vec<usize>> = links[start..end].iter().map(|targets| {
+			targets.iter().filter(|t| (start..end).contains(*t)).map(|t| t - start).collect()
+		}).collect();
+		domain.page_ranks = page_rank::scale_page_ranks(&page_rank::get_page_ranks(&local_links, &options.page_rank));
+		if !global_page_ranks.is_empty() {
+			domain.global_page_ranks = global_page_ranks[start..end].to_vec();
+		}
+		domain.anchors = anchors.drain(..domain.pages.len()).collect();
+		start = end;
+	}
+	Ok(())
+}
+
+// Splits a page into one part per section with an anchor, so that long pages
+// like single page manuals can be found section by section. The first part
+// keeps the URL and title of the page.
+fn get_page_parts<'a>(page: &Page, webpage: &'a Webpage, split: bool) -> Vec<PagePart<'a>> {
+	if !split || webpage.sections.len() < 2 {
+		return vec![PagePart {
+			url: page.url.clone(),
+			title: &webpage.title,
+			parent_title: None,
+			headings: &webpage.headings,
+			text: Cow::Borrowed(&webpage.text),
+			code: Cow::Borrowed(&webpage.code),
+			sections: &webpage.sections,
+		}]
+	}
+
+	let mut parts: Vec<PagePart> = Vec::new();
+	for section in &webpage.sections {
+		match parts.last_mut() {
+			// Text without its own anchor stays with the previous part
+			Some(part) if section.id.is_empty() => {
+				for (joined, added) in [(&mut part.text, &section.text), (&mut part.code, &section.code)] {
+					let joined = joined.to_mut();
+					joined.push('\n');
+					joined.push_str(added);
+				}
+			},
+			Some(_) => parts.push(PagePart {
+				url: format!("{}#{}", page.url, section.id),
+				title: if section.heading.is_empty() { &webpage.title } else { &section.heading },
+				parent_title: Some(&webpage.title),
+				headings: "",
+				text: Cow::Borrowed(&section.text),
+				code: Cow::Borrowed(&section.code),
+				sections: &[],
+			}),
+			None => parts.push(PagePart {
+				url: page.url.clone(),
+				title: &webpage.title,
+				parent_title: None,
+				headings: &webpage.headings,
+				text: Cow::Borrowed(&section.text),
+				code: Cow::Borrowed(&section.code),
+				sections: &[],
+			}),
+		}
+	}
+	parts
+}
+
+// URLs are compared by their keys, so that a page listed in a sitemap under a
+// slightly different URL still counts
+fn get_coverage(domain: &Domain) -> Coverage {
+	let indexed: HashSet<String> = domain.pages.iter()
+		.flat_map(|p| std::iter::once(&p.url).chain(&p.aliases))
+		.map(|u| urls::get_key(u))
+		.collect();
+	let mut missing: Vec<String> = domain.sitemap_urls.iter()
+		.filter(|u| !indexed.contains(&urls::get_key(u)))
+		.cloned()
+		.collect();
+	missing.sort_unstable();
+	Coverage {domain: domain.name.clone(), sitemap_url_count: domain.sitemap_urls.len(), missing}
+}
+
+fn get_versions(pages: &[Page], rules: &VersionRules) -> Vec<Option<(String, u64)>> {
+	let versions: Vec<Option<String>> = pages.iter().map(|p| rules.get_version(&p.url)).collect();
+	let mut distinct: Vec<&str> = versions.iter().flatten().map(|v| v.as_str()).collect();
+	distinct.sort_unstable_by(|a, b| versions::compare_versions(b, a));
+	distinct.dedup();
+	versions.iter().map(|version| {
+		let version = version.as_ref()?;
+		let age = distinct.iter().position(|v| v == version).unwrap();
+		Some((version.clone(), age as u64))
+	}).collect()
+}
 
-	let damp = 0.85;
-	let iterations = 16;
-	let inv_pages_len = 1.0 / pages.len() as f64;
-	for _ in 0..iterations {
-		let mut new_page_ranks = Vec::new();
-		for node in &graph {
-			let sum: f64 = node.iter().map(|n| page_ranks[*n] * outbound[*n]).sum();
-			new_page_ranks.push((1.0 - damp) * inv_pages_len + damp * sum);
+// Hash of everything indexed for a document except its page ranks, which
+// change with the links of other pages. The hash is stored in the index, so it
+// must stay the same between builds.
+fn get_document_hash(domain: &str, part: &PagePart, aliases: &[String], anchors: &str, version: &Option<(String, u64)>) -> u64 {
+	let version = version.as_ref().map(|(v, age)| format!("{}\t{}", v, age)).unwrap_or_default();
+	let mut fields = vec![domain, &part.url, part.title, part.parent_title.unwrap_or(""), part.headings, &part.text, &part.code, anchors, &version];
+	fields.extend(aliases.iter().map(|a| a.as_str()));
+	let sections: Vec<String> = part.sections.iter().map(|s| format!("{}\t{}\t{}", s.level, s.id, s.heading)).collect();
+	fields.extend(sections.iter().map(|s| s.as_str()));
+	fingerprint::get_hash(&fields)
+}
+
+// Content hash of each document in an existing index, by URL
+fn get_content_hashes(index: &tantivy::Index) -> tantivy::Result<HashMap<String, u64>> {
+	let searcher = index.reader()?.searcher();
+	let mut hashes = HashMap::new();
+	for segment_reader in searcher.segment_readers() {
+		let Some(urls) = segment_reader.fast_fields().str("url")? else {
+			continue
+		};
+		let content_hashes = segment_reader.fast_fields().u64("content_hash")?.first_or_default_col(0);
+		for doc in segment_reader.doc_ids_alive() {
+			let mut url = String::new();
+			if let Some(ord) = urls.term_ords(doc).next() {
+				urls.ord_to_str(ord, &mut url)?;
+			}
+			hashes.insert(url, content_hashes.get_val(doc));
 		}
-		debug_assert!(new_page_ranks.len() == page_ranks.len());
-		page_ranks = new_page_ranks;
 	}
+	Ok(hashes)
+}
+
+fn get_content_hash(webpage: &Webpage) -> u64 {
+	let mut hasher = DefaultHasher::new();
+	(&webpage.title, &webpage.headings, &webpage.text, &webpage.code, &webpage.sections).hash(&mut hasher);
+	hasher.finish()
+}
+
+// Adds the text of a link to the anchor text of the page it points to. Pages
+// often have many links with the same text, e.g. from navigation menus, so
+// repeated text is only added once.
+fn add_anchor_text(anchors: &mut String, text: &str) {
+	let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+	if text.is_empty() || anchors.len() + text.len() > MAX_ANCHOR_TEXT_LENGTH {
+		return
+	}
+	if anchors.split('\n').any(|a| a == text) {
+		return
+	}
+	if !anchors.is_empty() {
+		anchors.push('\n');
+	}
+	anchors.push_str(&text);
+}
 
-	page_ranks
+// Applies f to every item using a pool of threads, and returns the results in
+// the same order as the items. Stops at the first error.
+fn map_parallel<T, R, F>(items: &[T], threads: usize, f: F) -> tantivy::Result<Vec<R>>
+where
+	T: Sync,
+	R: Send,
+	F: Fn(&T) -> tantivy::Result<R> + Sync,
+{
+	let mut results = Vec::with_capacity(items.len());
+	for_each_parallel(items, threads, f, |result| results.push(result))?;
+	Ok(results)
+}
+
+// Applies f to every item using a pool of threads, and passes the results to
+// consume on the calling thread in the same order as the items, without
+// waiting for the other items. Stops at the first error.
+fn for_each_parallel<T, R, F, C>(items: &[T], threads: usize, f: F, mut consume: C) -> tantivy::Result<()>
+where
+	T: Sync,
+	R: Send,
+	F: Fn(&T) -> tantivy::Result<R> + Sync,
+	C: FnMut(R),
+{
+	let next = AtomicUsize::new(0);
+	let failed = AtomicBool::new(false);
+	std::thread::scope(|scope| {
+		let (sender, receiver) = mpsc::channel();
+		let workers: Vec<_> = (0..threads).map(|_| {
+			let sender = sender.clone();
+			let (next, failed, f) = (&next, &failed, &f);
+			scope.spawn(move || {
+				loop {
+					let i = next.fetch_add(1, Ordering::Relaxed);
+					if i >= items.len() || failed.load(Ordering::Relaxed) {
+						return Ok(())
+					}
+					match f(&items[i]) {
+						Ok(result) => {
+							if sender.send((i, result)).is_err() {
+								return Ok(())
+							}
+						},
+						Err(e) => {
+							failed.store(true, Ordering::Relaxed);
+							return Err(e)
+						},
+					}
+				}
+			})
+		}).collect();
+		drop(sender);
+
+		// Results that arrive before the ones of earlier items wait here
+		let mut pending = HashMap::new();
+		let mut next_result = 0;
+		for (i, result) in receiver {
+			pending.insert(i, result);
+			while let Some(result) = pending.remove(&next_result) {
+				consume(result);
+				next_result += 1;
+			}
+		}
+		workers.into_iter().try_for_each(|w| w.join().unwrap())
+	})
+}
+
+fn format_time(time: SystemTime) -> String {
+	let date_time: DateTime<Utc> = time.into();
+	date_time.to_rfc3339()
+}
+
+fn parse_time(time: &str) -> Option<SystemTime> {
+	Some(DateTime::parse_from_rfc3339(time).ok()?.into())
 }
 
 fn get_path_name(path: PathBuf) -> String {
 	path.file_name().unwrap().to_str().unwrap().to_string()
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn get_page(url: &str) -> Page {
+		Page {
+			url: url.to_string(),
+			aliases: Vec::new(),
+			crawled_url: url.to_string(),
+			source: PageSource::Files {path: PathBuf::new(), html_path: None},
+			size: 0,
+		}
+	}
+
+	fn get_section(id: &str, heading: &str, text: &str) -> Section {
+		Section {id: id.to_string(), heading: heading.to_string(), level: 2, text: text.to_string(), code: String::new()}
+	}
+
+	fn get_webpage(sections: Vec<Section>) -> Webpage {
+		Webpage {
+			url: String::new(),
+			domain: String::new(),
+			links: Vec::new(),
+			title: "Manual".to_string(),
+			text: "intro one two".to_string(),
+			headings: "One Two".to_string(),
+			code: String::new(),
+			sections,
+		}
+	}
+
+	#[test]
+	fn splits_pages_into_sections() {
+		let page = get_page("https://example.com/manual.html");
+		let webpage = get_webpage(vec![
+			get_section("", "", "intro"),
+			get_section("one", "One", "one"),
+			get_section("", "", "more about one"),
+			get_section("two", "", "two"),
+		]);
+		let parts = get_page_parts(&page, &webpage, true);
+		let summary: Vec<_> = parts.iter()
+			.map(|p| (p.url.as_str(), p.title, p.parent_title, p.text.as_ref()))
+			.collect();
+		assert_eq!(summary, [
+			("https://example.com/manual.html", "Manual", None, "intro"),
+			("https://example.com/manual.html#one", "One", Some("Manual"), "one\nmore about one"),
+			("https://example.com/manual.html#two", "Manual", Some("Manual"), "two"),
+		]);
+		assert_eq!(parts[0].headings, "One Two");
+		assert_eq!(parts[1].headings, "");
+	}
+
+	#[test]
+	fn keeps_pages_whole_unless_split() {
+		let page = get_page("https://example.com/manual.html");
+		let webpage = get_webpage(vec![get_section("", "", "intro"), get_section("one", "One", "one")]);
+		let parts = get_page_parts(&page, &webpage, false);
+		assert_eq!(parts.len(), 1);
+		assert_eq!((parts[0].url.as_str(), parts[0].title, parts[0].parent_title), ("https://example.com/manual.html", "Manual", None));
+		assert_eq!(parts[0].text, "intro one two");
+		assert_eq!(parts[0].sections.len(), 2);
+
+		let webpage = get_webpage(vec![get_section("one", "One", "one")]);
+		assert_eq!(get_page_parts(&page, &webpage, true).len(), 1);
+	}
+}
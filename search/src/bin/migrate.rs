@@ -0,0 +1,110 @@
+use std::fs;
+use std::collections::HashMap;
+use tantivy::{TantivyDocument, IndexWriter};
+use tantivy::schema::Value;
+use mini_search_engine::index;
+use mini_search_engine::suggest::SuggestionsBuilder;
+use mini_search_engine::synonyms::SYNONYMS_FILE;
+
+// Converts an index created with an older schema to the current schema by
+// copying the stored fields of each document. Fields which were not stored in
+// the old index can't be reconstructed, so they are left empty and reported.
+fn main() -> tantivy::Result<()> {
+	let args: Vec<String> = std::env::args().collect();
+	if args.len() != 3 {
+		eprintln!("Usage: {} OLD_INDEX NEW_INDEX", args.first().unwrap_or(&"migrate".to_string()));
+		std::process::exit(1);
+	}
+	let old_path = args[1].as_str();
+	let new_path = args[2].as_str();
+
+	// The old index is opened without checking its schema, and its
+	// tokenizers are not needed since only stored fields are read
+	let old_manifest = index::IndexManifest::open(old_path)?;
+	let old_index = tantivy::Index::open_in_dir(old_path)?;
+	let old_schema = old_index.schema();
+	println!("Migrating {} from schema version {} to {}...", old_path, old_manifest.schema_version, index::SCHEMA_VERSION);
+
+	let schema = index::get_schema();
+	// Everything about how the index was built is kept, so that it gives the
+	// same results as an index built again with the same settings
+	let manifest = index::IndexManifest {
+		manifest_version: index::MANIFEST_VERSION,
+		schema_version: index::SCHEMA_VERSION,
+		indexer_version: env!("CARGO_PKG_VERSION").to_string(),
+		..old_manifest.clone()
+	};
+	let index = index::create_index(&schema, new_path, &manifest)?;
+	let new_schema = index.schema();
+
+	// Fields are matched by name
+	let mut field_map = HashMap::new();
+	let mut lost_fields = Vec::new();
+	for (new_field, new_entry) in new_schema.fields() {
+		let name = new_entry.name();
+		let Ok(old_field) = old_schema.get_field(name) else {
+			lost_fields.push(format!("{} (new field)", name));
+			continue
+		};
+		let old_entry = old_schema.get_field_entry(old_field);
+		if !old_entry.is_stored() {
+			lost_fields.push(format!("{} (not stored)", name));
+		} else if old_entry.field_type().value_type() != new_entry.field_type().value_type() {
+			lost_fields.push(format!("{} (type changed)", name));
+		} else {
+			field_map.insert(old_field, new_field);
+		}
+	}
+	let dropped_fields: Vec<_> = old_schema.fields()
+		.map(|(_, entry)| entry.name())
+		.filter(|name| new_schema.get_field(name).is_err())
+		.collect();
+
+	let reader = old_index.reader()?;
+	let searcher = reader.searcher();
+	let mut writer: IndexWriter = index.writer(512 * 1024 * 1024)?;
+	let mut suggestions = SuggestionsBuilder::default();
+	let mut document_count = 0;
+	for segment_reader in searcher.segment_readers() {
+		let store_reader = segment_reader.get_store_reader(64)?;
+		for old_document in store_reader.iter::<TantivyDocument>(segment_reader.alive_bitset()) {
+			let old_document = old_document?;
+			let mut document = TantivyDocument::new();
+			for field_value in old_document.field_values() {
+				if let Some(field) = field_map.get(&field_value.field()) {
+					document.add_field_value(*field, field_value.value().clone());
+				}
+			}
+
+			let title = document.get_first(schema.title).and_then(|v| v.as_str());
+			let page_rank = document.get_first(schema.page_rank).and_then(|v| v.as_u64());
+			if let (Some(title), Some(page_rank)) = (title, page_rank) {
+				suggestions.add(title, page_rank);
+			}
+
+			writer.add_document(document)?;
+			document_count += 1;
+		}
+	}
+
+	println!("Committing...");
+	writer.commit()?;
+
+	println!("Writing suggestions...");
+	suggestions.write(new_path).map_err(|e| tantivy::TantivyError::SystemError(e.to_string()))?;
+
+	let old_synonyms = old_path.to_string() + "/" + SYNONYMS_FILE;
+	if fs::metadata(&old_synonyms).is_ok() {
+		fs::copy(&old_synonyms, new_path.to_string() + "/" + SYNONYMS_FILE)?;
+	}
+
+	println!("Migrated {} documents", document_count);
+	if !lost_fields.is_empty() {
+		println!("Fields that could not be reconstructed: {}", lost_fields.join(", "));
+		println!("Rebuild the index from the crawler output to restore them.");
+	}
+	if !dropped_fields.is_empty() {
+		println!("Fields no longer in the schema: {}", dropped_fields.join(", "));
+	}
+	Ok(())
+}
@@ -1,8 +1,11 @@
+use std::sync::Arc;
 use std::time::Instant;
 use actix_web::{web, App, HttpResponse, HttpRequest, HttpServer, Responder};
 use askama_actix::Template;
 use mini_search_engine::search;
 use mini_search_engine::index;
+use mini_search_engine::suggest::Suggestions;
+use mini_search_engine::synonyms::Synonyms;
 
 #[derive(Template)]
 #[template(path = "search.html")]
@@ -18,7 +21,15 @@ struct StatsTemplate {
 	creation_time: String,
 	index_size: String,
 	index_page_count: u64,
-	domain_page_counts: Vec<(String, u64)>,
+	schema_version: u32,
+	indexer_version: String,
+	domains: Vec<index::DomainStatistics>,
+}
+
+#[derive(Template)]
+#[template(path = "opensearch.xml")]
+struct OpenSearchTemplate<'a> {
+	base_url: &'a str,
 }
 
 #[derive(serde_derive::Deserialize)]
@@ -26,19 +37,42 @@ struct SearchQuery {
 	q: Option<String>,
 }
 
+// Number of completions returned by /api/suggest
+const SUGGESTION_COUNT: usize = 8;
+
 #[derive(Clone)]
 struct AppData {
 	schema: index::SearchEngineSchema,
 	index: tantivy::Index,
+	suggestions: Arc<Suggestions>,
+	synonyms: Arc<Synonyms>,
 	stats: String,
+
+	// Public URL of the search engine, e.g. "https://search.example.com".
+	// If not configured, it is taken from each request.
+	public_url: Option<String>,
 }
 
 impl AppData {
-	fn initialize(index_path: &str) -> tantivy::Result<AppData> {
+	fn initialize(index_path: &str, public_url: Option<&str>) -> tantivy::Result<AppData> {
 		let schema = index::get_schema();
 		let index = index::open_index(index_path)?;
 		let stats = get_stats_template(&schema, &index, index_path)?.render().unwrap();
-		Ok(AppData {schema, index, stats})
+		let suggestions = Suggestions::open(index_path).unwrap_or_else(|e| {
+			eprintln!("Autocomplete disabled, could not load suggestions: {}", e);
+			Suggestions::empty()
+		});
+		let mut analyzer = index.tokenizers().get("text").unwrap();
+		let synonyms = Synonyms::load(index_path, &mut analyzer)?;
+		let public_url = public_url.map(|url| url.trim_end_matches('/').to_string());
+		Ok(AppData {
+			schema,
+			index,
+			suggestions: Arc::new(suggestions),
+			synonyms: Arc::new(synonyms),
+			stats,
+			public_url,
+		})
 	}
 }
 
@@ -48,7 +82,9 @@ fn get_stats_template(schema: &index::SearchEngineSchema, index: &tantivy::Index
 		creation_time: stats.creation_time,
 		index_size: format!("{:.1} MiB", stats.size as f32 * 2f32.powf(-20f32)),
 		index_page_count: stats.page_count,
-		domain_page_counts: stats.domain_page_counts,
+		schema_version: stats.schema_version,
+		indexer_version: stats.indexer_version,
+		domains: stats.domains,
 	})
 }
 
@@ -57,11 +93,11 @@ async fn serve_search(query: web::Query<SearchQuery>, data: web::Data<AppData>)
 	let q = query.q.as_ref().map_or("", |s| s.as_str());
 
 	let time = Instant::now();
-	let results = search::search(&data.schema, &data.index, q);
+	let results = search::search(&data.schema, &data.index, &data.synonyms, q);
 	let latency = format!("{} seconds", time.elapsed().subsec_micros() as f32 * 0.000001f32);
 
 	let tmpl = SearchTemplate {
-		query: &q,
+		query: q,
 		latency: &latency,
 		results: match results {
 			None => search::SearchResults::Error("Internal server error".to_string()),
@@ -75,6 +111,30 @@ async fn serve_search(query: web::Query<SearchQuery>, data: web::Data<AppData>)
 	HttpResponse::Ok().body(tmpl.render().unwrap())
 }
 
+#[actix_web::get("/api/suggest")]
+async fn serve_suggest(query: web::Query<SearchQuery>, data: web::Data<AppData>) -> impl Responder {
+	let q = query.q.as_ref().map_or("", |s| s.as_str());
+	web::Json(data.suggestions.suggest(q, SUGGESTION_COUNT))
+}
+
+// OpenSearch suggestions format: the query followed by a list of completions
+#[actix_web::get("/api/suggest/opensearch")]
+async fn serve_opensearch_suggest(query: web::Query<SearchQuery>, data: web::Data<AppData>) -> impl Responder {
+	let q = query.q.as_ref().map_or("", |s| s.as_str());
+	let body = serde_json::json!([q, data.suggestions.suggest(q, SUGGESTION_COUNT)]);
+	HttpResponse::Ok().content_type("application/x-suggestions+json").body(body.to_string())
+}
+
+#[actix_web::get("/opensearch.xml")]
+async fn serve_opensearch(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+	let base_url = data.public_url.clone().unwrap_or_else(|| {
+		let info = req.connection_info();
+		format!("{}://{}", info.scheme(), info.host())
+	});
+	let tmpl = OpenSearchTemplate {base_url: &base_url};
+	HttpResponse::Ok().content_type("application/opensearchdescription+xml").body(tmpl.render().unwrap())
+}
+
 #[actix_web::get("/stats")]
 async fn serve_stats(data: web::Data<AppData>) -> impl Responder {
 	HttpResponse::Ok().body(data.stats.clone())
@@ -85,6 +145,7 @@ async fn serve_default(req: HttpRequest) -> impl Responder {
 		"/" => include_bytes!("../html/static/index.html"),
 		"/syntax" => include_bytes!("../html/static/syntax.html"),
 		"/style.css" => include_bytes!("../html/static/style.css"),
+		"/suggest.js" => include_bytes!("../html/static/suggest.js"),
 		"/bookshelf.jpeg" => include_bytes!("../html/static/bookshelf.jpeg"),
 		_ => return HttpResponse::NotFound().body(&include_bytes!("../html/static/404.html")[..]),
 	};
@@ -94,14 +155,18 @@ async fn serve_default(req: HttpRequest) -> impl Responder {
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
 	let args: Vec<String> = std::env::args().collect();
-	if args.len() != 3 {
-		eprintln!("Usage: {} INDEX ADDRESS", args.get(0).unwrap_or(&"server".to_string()));
+	if args.len() != 3 && args.len() != 4 {
+		eprintln!("Usage: {} INDEX ADDRESS [PUBLIC_URL]", args.first().unwrap_or(&"server".to_string()));
 		std::process::exit(1);
 	}
 	let index_path = args[1].as_str();
 	let server_address = args[2].as_str();
+	let public_url = args.get(3).map(|s| s.as_str());
 
-	let app_data = AppData::initialize(index_path).unwrap();
+	let app_data = AppData::initialize(index_path, public_url).unwrap_or_else(|e| {
+		eprintln!("Could not open index {}: {}", index_path, e);
+		std::process::exit(1);
+	});
 
 	println!("Starting server at http://{}/", server_address);
 
@@ -110,6 +175,9 @@ async fn main() -> std::io::Result<()> {
 			.app_data(web::Data::new(app_data.clone()))
 			.default_service(web::route().to(serve_default))
 			.service(serve_search)
+			.service(serve_suggest)
+			.service(serve_opensearch_suggest)
+			.service(serve_opensearch)
 			.service(serve_stats)
 	}).bind(server_address)?.run().await
 }
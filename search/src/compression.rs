@@ -0,0 +1,165 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use flate2::bufread::GzDecoder;
+use flate2::write::GzEncoder;
+
+// Files of records, like WARC or JSON Lines files, can be compressed with
+// gzip or zstd. To find records again without decompressing the whole file,
+// they are written in several frames (gzip members or zstd frames), and a
+// record is found from the offset of its frame.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Compression {
+	None,
+	Gzip,
+	Zstd,
+}
+
+// Where a record starts: the offset of its frame in the file (0 for
+// uncompressed files), and its offset in the uncompressed frame
+#[derive(Clone, Copy)]
+pub struct Position {
+	pub frame: u64,
+	pub offset: u64,
+}
+
+// Reading a record decompresses its frame up to the record again, so records
+// can't start further than this into their frame. Files compressed as a single
+// stream by other tools would have to be decompressed from the start for every
+// record, so they are rejected once they get past this.
+const MAX_FRAME_OFFSET: u64 = 1024 * 1024;
+
+// The uncompressed data of one frame, keeping track of the position
+pub struct FrameReader<'a> {
+	path: &'a Path,
+	compression: Compression,
+	frame: u64,
+	inner: &'a mut dyn BufRead,
+	offset: u64,
+}
+
+impl Compression {
+	pub fn from_path(path: &Path) -> Compression {
+		match path.extension().and_then(|e| e.to_str()) {
+			Some("gz") => Compression::Gzip,
+			Some("zst") => Compression::Zstd,
+			_ => Compression::None,
+		}
+	}
+
+	// Compresses data as one frame, which can be appended to a file
+	pub fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+		match self {
+			Compression::None => Ok(data.to_vec()),
+			Compression::Gzip => {
+				let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+				encoder.write_all(data)?;
+				encoder.finish()
+			},
+			Compression::Zstd => zstd::encode_all(data, 0),
+		}
+	}
+}
+
+// Name of a file without its extension and compression, e.g. "docs.python.org"
+// for "docs.python.org.warc.gz" with the extension "warc"
+pub fn get_stem<'a>(path: &'a Path, extension: &str) -> Option<&'a str> {
+	let name = path.file_name()?.to_str()?;
+	let name = name.strip_suffix(".gz").or(name.strip_suffix(".zst")).unwrap_or(name);
+	name.strip_suffix(extension)?.strip_suffix('.')
+}
+
+// Calls f with each frame of a file in order
+pub fn read_frames(path: &Path, mut f: impl FnMut(&mut FrameReader) -> io::Result<()>) -> io::Result<()> {
+	let compression = Compression::from_path(path);
+	let mut input = Counter {inner: BufReader::new(File::open(path)?), count: 0};
+	if compression == Compression::None {
+		return f(&mut FrameReader {path, compression, frame: 0, inner: &mut input, offset: 0})
+	}
+	while !input.fill_buf()?.is_empty() {
+		let frame = input.count;
+		let mut decoder: Box<dyn BufRead + '_> = match compression {
+			Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(&mut input)?.single_frame())),
+			_ => Box::new(BufReader::new(GzDecoder::new(&mut input))),
+		};
+		f(&mut FrameReader {path, compression, frame, inner: &mut decoder, offset: 0})?;
+		// The frame is read to the end, even if f stopped early
+		io::copy(&mut decoder, &mut io::sink())?;
+	}
+	Ok(())
+}
+
+// Returns the uncompressed data of a file from a position to the end of its
+// frame
+pub fn open_at(path: &Path, position: Position) -> io::Result<Box<dyn BufRead>> {
+	let mut file = File::open(path)?;
+	let compression = Compression::from_path(path);
+	// Uncompressed files can be read from the record itself
+	let start = match compression {
+		Compression::None => position.frame + position.offset,
+		_ => position.frame,
+	};
+	file.seek(SeekFrom::Start(start))?;
+	let input = BufReader::new(file);
+	let mut reader: Box<dyn BufRead> = match compression {
+		Compression::None => return Ok(Box::new(input)),
+		Compression::Gzip => Box::new(BufReader::new(GzDecoder::new(input))),
+		Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(input)?.single_frame())),
+	};
+	io::copy(&mut reader.by_ref().take(position.offset), &mut io::sink())?;
+	Ok(reader)
+}
+
+impl FrameReader<'_> {
+	pub fn position(&self) -> io::Result<Position> {
+		if self.compression != Compression::None && self.offset > MAX_FRAME_OFFSET {
+			let message = format!("{} is compressed as a single stream rather than in frames, decompress it to read it", self.path.display());
+			return Err(io::Error::new(io::ErrorKind::InvalidData, message))
+		}
+		Ok(Position {frame: self.frame, offset: self.offset})
+	}
+}
+
+impl Read for FrameReader<'_> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let n = self.inner.read(buf)?;
+		self.offset += n as u64;
+		Ok(n)
+	}
+}
+
+impl BufRead for FrameReader<'_> {
+	fn fill_buf(&mut self) -> io::Result<&[u8]> {
+		self.inner.fill_buf()
+	}
+
+	fn consume(&mut self, amount: usize) {
+		self.offset += amount as u64;
+		self.inner.consume(amount);
+	}
+}
+
+// Counts the bytes consumed from a reader, to find the offsets of frames
+struct Counter<R> {
+	inner: R,
+	count: u64,
+}
+
+impl<R: BufRead> Read for Counter<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let n = self.inner.read(buf)?;
+		self.count += n as u64;
+		Ok(n)
+	}
+}
+
+impl<R: BufRead> BufRead for Counter<R> {
+	fn fill_buf(&mut self) -> io::Result<&[u8]> {
+		self.inner.fill_buf()
+	}
+
+	fn consume(&mut self, amount: usize) {
+		self.count += amount as u64;
+		self.inner.consume(amount);
+	}
+}
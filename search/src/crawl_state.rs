@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// Saved in the output directory of each domain, next to the pages
+pub const CRAWL_STATE_FILE: &str = "crawl_state.json";
+
+// When the pages of a domain are in one JSON Lines file, its state is saved
+// next to it, e.g. as "docs.python.org.crawl_state.json"
+pub fn get_domain_state_path(dir: &Path, domain: &str) -> PathBuf {
+	dir.join(format!("{}.{}", domain, CRAWL_STATE_FILE))
+}
+
+// Progress of the crawl of one domain, saved regularly so that an interrupted
+// crawl can continue where it stopped
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+pub struct CrawlState {
+	// RFC 3339 times
+	pub start: Option<String>,
+	pub end: Option<String>,
+
+	// Set once there are no URLs left to fetch
+	#[serde(default)]
+	pub complete: bool,
+
+	// URLs waiting to be fetched
+	#[serde(default)]
+	pub frontier: Vec<String>,
+
+	// URLs already fetched, or skipped
+	#[serde(default)]
+	pub visited: Vec<String>,
+
+	// Pages saved by earlier crawls, which are kept from one crawl to the next
+	#[serde(default)]
+	pub pages: HashMap<String, CachedPage>,
+
+	// Pages in the whitelist that sitemaps of the domain list, which should
+	// all end up in the index
+	#[serde(default)]
+	pub sitemap_urls: Vec<String>,
+}
+
+// What the server said about a saved page, to check whether it changed when
+// the domain is crawled again
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default)]
+pub struct CachedPage {
+	pub etag: Option<String>,
+	pub last_modified: Option<String>,
+
+	// Number of crawls in a row that got a 404 or 410 for the page
+	#[serde(default)]
+	pub missing: u32,
+
+	// Set once the page has been missing for long enough that its saved copy
+	// was deleted
+	#[serde(default)]
+	pub gone: bool,
+}
+
+impl CrawlState {
+	// Returns None if the domain has no saved state, e.g. if it was crawled
+	// before crawl states were saved
+	pub fn read(dir: &Path) -> std::io::Result<Option<CrawlState>> {
+		CrawlState::read_file(&dir.join(CRAWL_STATE_FILE))
+	}
+
+	pub fn read_file(path: &Path) -> std::io::Result<Option<CrawlState>> {
+		if !path.exists() {
+			return Ok(None)
+		}
+		Ok(Some(serde_json::from_str(&std::fs::read_to_string(path)?)?))
+	}
+
+	// The state is written to a temporary file first, so that an interruption
+	// never leaves a truncated state behind
+	pub fn write(&self, dir: &Path) -> std::io::Result<()> {
+		std::fs::create_dir_all(dir)?;
+		let temp_path = dir.join(format!("{}.tmp", CRAWL_STATE_FILE));
+		std::fs::write(&temp_path, serde_json::to_string(self)?)?;
+		std::fs::rename(temp_path, dir.join(CRAWL_STATE_FILE))
+	}
+}
@@ -0,0 +1,273 @@
+use std::collections::{HashMap, HashSet};
+use scraper::{ElementRef, Html, Selector};
+use url::Url;
+use crate::webpage::{Webpage, Link, Section};
+
+const DEFAULT_EXTRACT_RULES: &str = include_str!("../extract_rules.txt");
+
+// Pages with less text than this are usually index pages or errors, and are
+// not worth indexing
+pub const MIN_TEXT_LENGTH: usize = 200;
+
+const HEADING_TAGS: &[&str] = &["h1", "h2", "h3", "h4", "h5", "h6"];
+const CODE_TAGS: &[&str] = &["code", "tt", "pre", "kdb", "samp", "var"];
+const META_NAMES: &[&str] = &["keywords", "description"];
+
+// Main content and boilerplate selectors of every domain
+pub struct ExtractRules {
+	domains: HashMap<String, ContentRules>,
+}
+
+// Selectors for the pages of one domain. With no selectors, pages are
+// extracted the same way as by the Python spider.
+#[derive(Clone, Default)]
+pub struct ContentRules {
+	main: Vec<Selector>,
+	remove: Vec<Selector>,
+}
+
+impl Default for ExtractRules {
+	fn default() -> ExtractRules {
+		ExtractRules::parse(DEFAULT_EXTRACT_RULES).unwrap()
+	}
+}
+
+impl ExtractRules {
+	// See extract_rules.txt for the format
+	pub fn parse(text: &str) -> Result<ExtractRules, String> {
+		let mut domains: HashMap<String, ContentRules> = HashMap::new();
+		for (i, line) in text.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue
+			}
+			let mut parts = line.splitn(3, char::is_whitespace);
+			let (Some(domain), Some(kind), Some(selector)) = (parts.next(), parts.next(), parts.next()) else {
+				return Err(format!("line {}: expected a domain, \"main\" or \"remove\" and a selector", i + 1))
+			};
+			let selector = Selector::parse(selector.trim()).map_err(|e| format!("line {}: {}", i + 1, e))?;
+			let rules = domains.entry(domain.to_string()).or_default();
+			match kind {
+				"main" => rules.main.push(selector),
+				"remove" => rules.remove.push(selector),
+				_ => return Err(format!("line {}: unknown rule \"{}\"", i + 1, kind)),
+			}
+		}
+		Ok(ExtractRules {domains})
+	}
+
+	// Rules of the domain come before the rules for every domain
+	pub fn get(&self, domain: &str) -> ContentRules {
+		let mut rules = self.domains.get(domain).cloned().unwrap_or_default();
+		if let Some(all) = self.domains.get("*") {
+			rules.main.extend(all.main.iter().cloned());
+			rules.remove.extend(all.remove.iter().cloned());
+		}
+		rules
+	}
+}
+
+// Turns the HTML of a page into the fields the indexer reads. Without rules,
+// this follows the CSS selectors used by the Python spider:
+//
+// - title: "title::text"
+// - text: "body *:not(script):not(style)::text"
+// - headings: text in title and h1 to h6, and the keywords and description
+//   meta tags
+// - code: text in code, tt, pre, kdb, samp and var
+//
+// With rules, text, headings in the body, code and sections only come from the
+// main content, without the removed elements.
+//
+// Each piece of text is trimmed, and the non-empty ones are joined with
+// newlines. Links are resolved against the URL of the page, but not filtered.
+pub fn extract(url: &Url, html: &str, rules: &ContentRules) -> Webpage {
+	let mut document = Html::parse_document(html);
+	let links = get_links(url, &document);
+	remove_elements(&mut document, &rules.remove);
+	let content = get_content(&document, &rules.main);
+
+	let mut title = Vec::new();
+	let mut text = Vec::new();
+	let mut headings = Vec::new();
+	let mut code = Vec::new();
+
+	for node in document.root_element().descendants() {
+		if let Some(element) = ElementRef::wrap(node) {
+			let element = element.value();
+			let is_meta = element.name() == "meta" && element.attr("name").is_some_and(|n| META_NAMES.contains(&n));
+			if let (true, Some(content)) = (is_meta, element.attr("content")) {
+				headings.push(content);
+			}
+			continue
+		}
+		let Some(t) = node.value().as_text() else {
+			continue
+		};
+		let Some(parent) = node.parent().and_then(ElementRef::wrap) else {
+			continue
+		};
+		let ancestors: Vec<&str> = node.ancestors().filter_map(ElementRef::wrap).map(|e| e.value().name()).collect();
+		let parent = parent.value().name();
+		let in_content = content.is_some_and(|c| node.ancestors().any(|a| a.id() == c.id()));
+
+		if parent == "title" {
+			title.push(&**t);
+		}
+		if parent != "body" && parent != "script" && parent != "style" && in_content {
+			text.push(&**t);
+		}
+		if ancestors.contains(&"title") || (in_content && ancestors.iter().any(|a| HEADING_TAGS.contains(a))) {
+			headings.push(&**t);
+		}
+		if in_content && ancestors.iter().any(|a| CODE_TAGS.contains(a)) {
+			code.push(&**t);
+		}
+	}
+
+	Webpage {
+		url: url.to_string(),
+		domain: get_netloc(url),
+		links,
+		title: join_text(&title),
+		text: join_text(&text),
+		headings: join_text(&headings),
+		code: join_text(&code),
+		sections: content.map(get_sections).unwrap_or_default(),
+	}
+}
+
+fn remove_elements(document: &mut Html, selectors: &[Selector]) {
+	let ids: Vec<_> = selectors.iter().flat_map(|s| document.select(s).map(|e| e.id()).collect::<Vec<_>>()).collect();
+	for id in ids {
+		if let Some(mut node) = document.tree.get_mut(id) {
+			node.detach();
+		}
+	}
+}
+
+// The first element matching a main content selector, or else the body
+fn get_content<'a>(document: &'a Html, main: &[Selector]) -> Option<ElementRef<'a>> {
+	main.iter()
+		.find_map(|s| document.select(s).next())
+		.or_else(|| document.root_element().children().filter_map(ElementRef::wrap).find(|e| e.value().name() == "body"))
+}
+
+fn join_text<S: AsRef<str>>(parts: &[S]) -> String {
+	parts.iter().map(|p| p.as_ref().trim()).filter(|p| !p.is_empty()).collect::<Vec<_>>().join("\n")
+}
+
+// Whitespace is collapsed, since anchor text and headings often span lines
+fn get_inner_text(element: ElementRef) -> String {
+	element.text().collect::<Vec<_>>().join(" ").split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Host of the URL, with the port if it isn't the default, like Python's
+// urlparse(url).netloc
+pub fn get_netloc(url: &Url) -> String {
+	let host = url.host_str().unwrap_or("");
+	match url.port() {
+		Some(port) => format!("{}:{}", host, port),
+		None => host.to_string(),
+	}
+}
+
+// Every link target with each different text used for it
+fn get_links(url: &Url, document: &Html) -> Vec<Link> {
+	let mut seen = HashSet::new();
+	let mut links = Vec::new();
+	for element in document.root_element().descendants().filter_map(ElementRef::wrap) {
+		if element.value().name() != "a" {
+			continue
+		}
+		let Some(href) = element.value().attr("href") else {
+			continue
+		};
+		// Basic check for special HTML hrefs
+		if ["#", "mailto:", "data:", "javascript:"].iter().any(|p| href.starts_with(p)) {
+			continue
+		}
+		let Ok(target) = url.join(href) else {
+			continue
+		};
+		let link = (target.to_string(), get_inner_text(element));
+		if seen.insert(link.clone()) {
+			links.push(Link::Anchor {url: link.0, text: link.1});
+		}
+	}
+	links
+}
+
+// Splits the content into sections starting at headings with an anchor.
+// Headings without an anchor can't be linked to, so they are just part of the
+// current section.
+fn get_sections(content: ElementRef) -> Vec<Section> {
+	let mut sections = vec![Section {
+		id: String::new(),
+		heading: String::new(),
+		level: 0,
+		text: String::new(),
+		code: String::new(),
+	}];
+	let mut text: Vec<Vec<String>> = vec![Vec::new()];
+	let mut code: Vec<Vec<String>> = vec![Vec::new()];
+
+	for node in content.descendants() {
+		if let Some(element) = ElementRef::wrap(node) {
+			let name = element.value().name();
+			if !HEADING_TAGS.contains(&name) || is_inside_heading(element) {
+				continue
+			}
+			let heading = get_inner_text(element);
+			let Some(id) = get_anchor(element) else {
+				text.last_mut().unwrap().push(heading);
+				continue
+			};
+			sections.push(Section {
+				id: id.to_string(),
+				heading,
+				level: name[1..].parse().unwrap(),
+				text: String::new(),
+				code: String::new(),
+			});
+			text.push(Vec::new());
+			code.push(Vec::new());
+			continue
+		}
+
+		let Some(t) = node.value().as_text() else {
+			continue
+		};
+		let ancestors: Vec<&str> = node.ancestors().filter_map(ElementRef::wrap).map(|e| e.value().name()).collect();
+		if ancestors.iter().any(|a| *a == "script" || *a == "style" || HEADING_TAGS.contains(a)) {
+			continue
+		}
+		text.last_mut().unwrap().push(t.to_string());
+		if ancestors.iter().any(|a| CODE_TAGS.contains(a)) {
+			code.last_mut().unwrap().push(t.to_string());
+		}
+	}
+
+	for (section, (text, code)) in sections.iter_mut().zip(text.iter().zip(&code)) {
+		section.text = join_text(text);
+		section.code = join_text(code);
+	}
+	sections.into_iter().filter(|s| !s.id.is_empty() || !s.text.is_empty()).collect()
+}
+
+fn is_inside_heading(element: ElementRef) -> bool {
+	element.ancestors().filter_map(ElementRef::wrap).any(|e| HEADING_TAGS.contains(&e.value().name()))
+}
+
+// The anchor of a heading is either an id on the heading or inside it, or a
+// link target right before it, e.g. <a name="intro"></a><h2>Introduction</h2>
+fn get_anchor<'a>(heading: ElementRef<'a>) -> Option<&'a str> {
+	let previous = heading.prev_siblings().filter_map(ElementRef::wrap).next();
+	if let Some(previous) = previous.filter(|p| p.value().name() == "a") {
+		let anchor = previous.value().attrs().find(|(name, _)| *name == "name" || *name == "id");
+		if let Some((_, anchor)) = anchor {
+			return Some(anchor)
+		}
+	}
+	heading.descendants().filter_map(ElementRef::wrap).find_map(|e| e.value().attr("id"))
+}
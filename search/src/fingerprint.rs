@@ -0,0 +1,103 @@
+// Pages whose fingerprints differ in at most this many bits are considered
+// near-duplicates, e.g. the same page in the docs for two versions of Python
+pub const MAX_SIMILAR_DISTANCE: u32 = 3;
+
+// Number of consecutive words hashed together as one feature
+const SHINGLE_SIZE: usize = 2;
+
+// Computes a SimHash of the words in a text, so that similar texts get
+// fingerprints which differ in only a few bits. Returns 0 for texts without
+// words, which is never considered similar to anything.
+pub fn get_fingerprint(text: &str) -> u64 {
+	let words: Vec<String> = text.split(|c: char| !c.is_alphanumeric())
+		.filter(|w| !w.is_empty())
+		.map(|w| w.to_lowercase())
+		.collect();
+	if words.is_empty() {
+		return 0
+	}
+
+	let mut weights = [0i64; 64];
+	for shingle in words.windows(SHINGLE_SIZE.min(words.len())) {
+		let hash = get_hash(shingle);
+		for (bit, weight) in weights.iter_mut().enumerate() {
+			if hash & (1 << bit) != 0 {
+				*weight += 1;
+			} else {
+				*weight -= 1;
+			}
+		}
+	}
+
+	let fingerprint = weights.iter().enumerate()
+		.filter(|(_, weight)| **weight > 0)
+		.fold(0, |f, (bit, _)| f | (1 << bit));
+	// 0 is left for pages without a fingerprint
+	fingerprint.max(1)
+}
+
+pub fn is_similar(a: u64, b: u64) -> bool {
+	a != 0 && b != 0 && (a ^ b).count_ones() <= MAX_SIMILAR_DISTANCE
+}
+
+// 64-bit FNV-1a, which unlike the standard library's hasher is guaranteed to
+// give the same result in every build, so fingerprints and content hashes
+// stored in an index stay comparable
+pub fn get_hash<S: AsRef<str>>(words: &[S]) -> u64 {
+	let mut hash: u64 = 0xcbf29ce484222325;
+	for word in words {
+		for byte in word.as_ref().bytes().chain(std::iter::once(0)) {
+			hash ^= byte as u64;
+			hash = hash.wrapping_mul(0x100000001b3);
+		}
+	}
+	hash
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const PAGE_TEXT: &str = "os.path — Common pathname manipulations. This module implements some useful \
+		functions on pathnames. To read or write files see open(), and for accessing the filesystem see \
+		the os module. The path parameters can be passed as strings, or bytes, or any object implementing \
+		the os.PathLike protocol. Unlike a Unix shell, Python does not do any automatic path expansions. \
+		Functions such as expanduser() and expandvars() can be invoked explicitly when an application \
+		desires shell-like path expansion. os.path.abspath(path) returns a normalized absolutized version \
+		of the pathname path. On most platforms, this is equivalent to calling the function normpath() as \
+		follows: normpath(join(os.getcwd(), path)). os.path.basename(path) returns the base name of \
+		pathname path. This is the second element of the pair returned by passing path to the function \
+		split(). Note that the result of this function is different from the Unix basename program.";
+
+	const OTHER_TEXT: &str = "Getting started with Bootstrap, a powerful, feature-packed frontend toolkit. \
+		Build anything from prototype to production in minutes. Create a new index.html file in your \
+		project root and include the viewport meta tag for proper responsive behavior in mobile devices. \
+		Include the CSS and JavaScript from the CDN in the head and at the end of the body. Then add \
+		components like the navbar, cards, modals and dropdowns, and customize the colors and spacing \
+		with Sass variables, maps and mixins, or with the CSS variables set on the root element.";
+
+	#[test]
+	fn near_duplicates_are_similar() {
+		let page = get_fingerprint(PAGE_TEXT);
+		let other_version = get_fingerprint(&PAGE_TEXT.replace("Python", "Python 3.12"));
+		assert!((page ^ other_version).count_ones() <= MAX_SIMILAR_DISTANCE);
+		assert!(is_similar(page, other_version));
+		assert!(is_similar(page, get_fingerprint(&PAGE_TEXT.to_uppercase())));
+	}
+
+	#[test]
+	fn unrelated_texts_are_not_similar() {
+		let page = get_fingerprint(PAGE_TEXT);
+		let other = get_fingerprint(OTHER_TEXT);
+		assert!((page ^ other).count_ones() > MAX_SIMILAR_DISTANCE);
+		assert!(!is_similar(page, other));
+	}
+
+	#[test]
+	fn texts_without_words_are_never_similar() {
+		assert_eq!(get_fingerprint(" — ... "), 0);
+		assert!(!is_similar(0, 0));
+		assert!(!is_similar(0, get_fingerprint(PAGE_TEXT)));
+		assert!(is_similar(get_fingerprint("one"), get_fingerprint("one")));
+	}
+}
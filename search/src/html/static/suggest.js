@@ -0,0 +1,37 @@
+// Show completions from /api/suggest below the search box. The search box
+// works the same without this script.
+(function() {
+	const input = document.querySelector("form.search input[name=q]");
+	if (!input || !window.fetch) {
+		return;
+	}
+
+	const list = document.createElement("datalist");
+	list.id = "suggestions";
+	input.after(list);
+	input.setAttribute("list", list.id);
+	input.setAttribute("autocomplete", "off");
+
+	let controller = null;
+	input.addEventListener("input", () => {
+		if (controller) {
+			controller.abort();
+		}
+		const q = input.value.trim();
+		if (!q) {
+			list.replaceChildren();
+			return;
+		}
+		controller = new AbortController();
+		fetch("/api/suggest?q=" + encodeURIComponent(q), {signal: controller.signal})
+			.then((response) => response.json())
+			.then((suggestions) => {
+				list.replaceChildren(...suggestions.map((s) => {
+					const option = document.createElement("option");
+					option.value = s;
+					return option;
+				}));
+			})
+			.catch(() => {});
+	});
+})();
@@ -1,35 +1,236 @@
 use std::fs;
 use chrono::{DateTime, Utc};
 use tantivy::schema::{Schema, Field, TextFieldIndexing, TextOptions, Term, IndexRecordOption, STRING, STORED, FAST};
-use tantivy::tokenizer::{TextAnalyzer, Token, Tokenizer, TokenStream,  LowerCaser, Stemmer, Language};
+use tantivy::tokenizer::{TextAnalyzer, Token, Tokenizer, TokenStream, LowerCaser, Stemmer, Language, AsciiFoldingFilter, StopWordFilter};
 use tantivy::query::{Query, TermQuery, AllQuery};
+use crate::page_rank::PageRankOptions;
 
 #[derive(Clone)]
 pub struct SearchEngineSchema {
 	handle: Schema,
 	pub domain: Field,
 	pub url: Field,
+	pub aliases: Field,
 	pub page_rank: Field,
+	pub global_page_rank: Field,
+	pub fingerprint: Field,
+	pub content_hash: Field,
+	pub version: Field,
+	pub version_age: Field,
 	pub title: Field,
+	pub parent_title: Field,
+	pub sections: Field,
 	pub headings: Field,
+	pub anchors: Field,
 	pub text: Field,
 	pub code: Field,
 }
 
+// Written to the index directory by the indexer
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+// Version of the manifest format
+pub const MANIFEST_VERSION: u32 = 1;
+
+// Must be incremented whenever get_schema changes, since an index can only be
+// opened with the schema it was created with. Older indexes can be converted
+// with the migrate tool.
+pub const SCHEMA_VERSION: u32 = 9;
+
+// Information about how and when the index was built. Indexes created before
+// the manifest existed only have a list of domains in domains.txt, which is
+// read as a manifest with the first schema version and the default analyzer.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct IndexManifest {
+	pub manifest_version: u32,
+	pub schema_version: u32,
+	pub indexer_version: String,
+
+	// RFC 3339 timestamp
+	pub build_time: Option<String>,
+
+	// Path to the crawler output the index was built from
+	pub crawler_input: String,
+
+	// Queries must be analyzed the same way as documents, so the server
+	// always uses the analyzer settings recorded here
+	pub analyzer: AnalyzerConfig,
+
+	#[serde(default)]
+	pub page_rank: PageRankOptions,
+
+	// Whether page ranks were also computed over the links between all
+	// domains, rather than only for each domain separately
+	#[serde(default)]
+	pub global_page_rank: bool,
+
+	// Domains whose pages were indexed as one document per section
+	#[serde(default)]
+	pub split_sections: Vec<String>,
+
+	pub domains: Vec<DomainManifest>,
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct DomainManifest {
+	pub name: String,
+	pub page_count: u64,
+
+	// Modification times of the oldest and newest crawled page as RFC 3339
+	// timestamps
+	pub crawl_start: Option<String>,
+	pub crawl_end: Option<String>,
+}
+
+// Settings for the "text" analyzer
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct AnalyzerConfig {
+	// No stemming if None
+	pub stemmer: Option<Language>,
+
+	// Convert accented characters to their ASCII equivalents (é -> e)
+	pub ascii_folding: bool,
+
+	// Stop words are still indexed so that phrases containing them can be
+	// found, but they are left out of search terms unless there is nothing
+	// else to search for. None if all search terms are kept.
+	pub stop_words: Option<StopWords>,
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum StopWords {
+	// Built-in list for a language, e.g. "English"
+	Language(Language),
+	List(Vec<String>),
+}
+
+impl Default for AnalyzerConfig {
+	fn default() -> AnalyzerConfig {
+		AnalyzerConfig {
+			stemmer: Some(Language::English),
+			ascii_folding: false,
+			stop_words: Some(StopWords::Language(Language::English)),
+		}
+	}
+}
+
+impl IndexManifest {
+	pub fn new(crawler_input: &str, analyzer: AnalyzerConfig) -> IndexManifest {
+		IndexManifest {
+			manifest_version: MANIFEST_VERSION,
+			schema_version: SCHEMA_VERSION,
+			indexer_version: env!("CARGO_PKG_VERSION").to_string(),
+			build_time: Some(Utc::now().to_rfc3339()),
+			crawler_input: crawler_input.to_string(),
+			analyzer,
+			page_rank: PageRankOptions::default(),
+			global_page_rank: false,
+			split_sections: Vec::new(),
+			domains: Vec::new(),
+		}
+	}
+
+	pub fn open(index_dir: &str) -> tantivy::Result<IndexManifest> {
+		let manifest_path = index_dir.to_string() + "/" + MANIFEST_FILE;
+		let contents = match fs::read_to_string(&manifest_path) {
+			Ok(contents) => contents,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => return IndexManifest::open_legacy(index_dir),
+			Err(e) => return Err(e.into()),
+		};
+		let manifest: IndexManifest = serde_json::from_str(&contents).map_err(|e| {
+			tantivy::TantivyError::InvalidArgument(format!("{}: {}", manifest_path, e))
+		})?;
+		if manifest.manifest_version > MANIFEST_VERSION {
+			return Err(tantivy::TantivyError::InvalidArgument(format!(
+				"{} has manifest version {}, but only versions up to {} are supported",
+				manifest_path, manifest.manifest_version, MANIFEST_VERSION)))
+		}
+		Ok(manifest)
+	}
+
+	fn open_legacy(index_dir: &str) -> tantivy::Result<IndexManifest> {
+		// Legacy indexes were all created with the first version of the
+		// schema
+		let domains_path = index_dir.to_string() + "/domains.txt";
+		let build_time = fs::metadata(&domains_path)?.created().ok().map(|t| {
+			let date_time: DateTime<Utc> = t.into();
+			date_time.to_rfc3339()
+		});
+		let domains = fs::read_to_string(&domains_path)?.lines().map(|name| DomainManifest {
+			name: name.to_string(),
+			page_count: 0,
+			crawl_start: None,
+			crawl_end: None,
+		}).collect();
+		Ok(IndexManifest {
+			manifest_version: MANIFEST_VERSION,
+			schema_version: 1,
+			indexer_version: "unknown".to_string(),
+			build_time,
+			crawler_input: "unknown".to_string(),
+			analyzer: AnalyzerConfig::default(),
+			page_rank: PageRankOptions::default(),
+			global_page_rank: false,
+			split_sections: Vec::new(),
+			domains,
+		})
+	}
+
+	pub fn write(&self, index_dir: &str) -> tantivy::Result<()> {
+		let manifest_path = index_dir.to_string() + "/" + MANIFEST_FILE;
+		fs::write(manifest_path, serde_json::to_string_pretty(self).unwrap())?;
+		Ok(())
+	}
+
+	pub fn check_compatible(&self, index_dir: &str) -> tantivy::Result<()> {
+		if self.schema_version != SCHEMA_VERSION {
+			return Err(tantivy::TantivyError::SchemaError(format!(
+				"index {} has schema version {}, but this program requires version {}; run migrate or rebuild the index",
+				index_dir, self.schema_version, SCHEMA_VERSION)))
+		}
+		Ok(())
+	}
+}
+
 pub struct IndexStatistics {
 	pub creation_time: String,
 	pub size: u64,
 	pub page_count: u64,
-	pub domain_page_counts: Vec<(String, u64)>,
+	pub schema_version: u32,
+	pub indexer_version: String,
+	pub domains: Vec<DomainStatistics>,
+}
+
+pub struct DomainStatistics {
+	pub name: String,
+	pub page_count: u64,
+	pub crawl_date: String,
 }
 
 pub fn get_schema() -> SearchEngineSchema {
 	let mut schema_builder = Schema::builder();
 	schema_builder.add_text_field("domain", STRING | STORED | FAST);
 	schema_builder.add_text_field("url", STRING | STORED | FAST);
+	schema_builder.add_text_field("aliases", STRING | STORED);
 	schema_builder.add_u64_field("page_rank", STORED | FAST);
+	schema_builder.add_u64_field("global_page_rank", STORED | FAST);
+	schema_builder.add_u64_field("fingerprint", STORED | FAST);
+	// Hash of everything indexed for the document except its page ranks, so
+	// that updating an index can skip documents that haven't changed
+	schema_builder.add_u64_field("content_hash", STORED | FAST);
+	schema_builder.add_text_field("version", STRING | STORED);
+	// Number of newer versions of the same domain, 0 for pages without a
+	// version
+	schema_builder.add_u64_field("version_age", STORED | FAST);
 	schema_builder.add_text_field("title", get_text_options("text").set_stored());
+	schema_builder.add_text_field("parent_title", STORED);
+	// Headings of the page which can be linked to, each stored as the heading
+	// level, anchor id and heading text separated by tabs
+	schema_builder.add_text_field("sections", STORED);
 	schema_builder.add_text_field("headings", get_text_options("text"));
+	schema_builder.add_text_field("anchors", get_text_options("text"));
 	schema_builder.add_text_field("text", get_text_options("text").set_stored());
 	schema_builder.add_text_field("code", get_text_options("code"));
 	let schema = schema_builder.build();
@@ -37,9 +238,18 @@ pub fn get_schema() -> SearchEngineSchema {
 		handle: schema.clone(),
 		domain: schema.get_field("domain").unwrap(),
 		url: schema.get_field("url").unwrap(),
+		aliases: schema.get_field("aliases").unwrap(),
 		page_rank: schema.get_field("page_rank").unwrap(),
+		global_page_rank: schema.get_field("global_page_rank").unwrap(),
+		fingerprint: schema.get_field("fingerprint").unwrap(),
+		content_hash: schema.get_field("content_hash").unwrap(),
+		version: schema.get_field("version").unwrap(),
+		version_age: schema.get_field("version_age").unwrap(),
 		title: schema.get_field("title").unwrap(),
+		parent_title: schema.get_field("parent_title").unwrap(),
+		sections: schema.get_field("sections").unwrap(),
 		headings: schema.get_field("headings").unwrap(),
+		anchors: schema.get_field("anchors").unwrap(),
 		text: schema.get_field("text").unwrap(),
 		code: schema.get_field("code").unwrap(),
 	}
@@ -52,62 +262,110 @@ fn get_text_options(tokenizer: &str) -> TextOptions {
 	TextOptions::default().set_indexing_options(indexing)
 }
 
-pub fn create_index(schema: &SearchEngineSchema, index_dir: &str) -> tantivy::Result<tantivy::Index> {
+// The manifest is written immediately, and should be written again once
+// indexing is finished to record the indexed domains
+pub fn create_index(schema: &SearchEngineSchema, index_dir: &str, manifest: &IndexManifest) -> tantivy::Result<tantivy::Index> {
 	fs::create_dir_all(index_dir)?;
 	let index = tantivy::Index::create_in_dir(index_dir, schema.handle.clone())?;
-	register_tokenizers(&index);
+	manifest.write(index_dir)?;
+	register_tokenizers(&index, &manifest.analyzer);
 	Ok(index)
 }
 
 pub fn open_index(index_dir: &str) -> tantivy::Result<tantivy::Index> {
+	let manifest = IndexManifest::open(index_dir)?;
+	manifest.check_compatible(index_dir)?;
 	let index = tantivy::Index::open_in_dir(index_dir)?;
-	register_tokenizers(&index);
+
+	// Legacy indexes have no recorded schema version, so the schema itself is
+	// compared too. Otherwise a mismatch would only show up later as a panic
+	// when looking up a field.
+	if index.schema() != get_schema().handle {
+		return Err(tantivy::TantivyError::SchemaError(format!(
+			"index {} does not have the schema of version {}; run migrate or rebuild the index",
+			index_dir, SCHEMA_VERSION)))
+	}
+
+	register_tokenizers(&index, &manifest.analyzer);
 	Ok(index)
 }
 
-fn register_tokenizers(index: &tantivy::Index) {
+fn register_tokenizers(index: &tantivy::Index, config: &AnalyzerConfig) {
 	let manager = index.tokenizers();
-	let text_analyzer = TextAnalyzer::builder(TextTokenizer::default())
-                .filter(LowerCaser)
-                .filter(Stemmer::new(Language::English))
-                .build();
-	manager.register("text", text_analyzer);
+	manager.register("text", get_text_analyzer(config, true, false));
+
+	// Used at search time for search terms, where stop words are optional
+	manager.register("text_without_stop_words", get_text_analyzer(config, true, true));
+
+	// Used at search time for prefix terms, which must not be stemmed
+	manager.register("text_prefix", get_text_analyzer(config, false, false));
+
 	manager.register("code", CodeTokenizer::default());
 }
 
+fn get_text_analyzer(config: &AnalyzerConfig, stem: bool, remove_stop_words: bool) -> TextAnalyzer {
+	let mut builder = TextAnalyzer::builder(TextTokenizer::default())
+		.filter(LowerCaser)
+		.dynamic();
+	if config.ascii_folding {
+		builder = builder.filter_dynamic(AsciiFoldingFilter);
+	}
+	if remove_stop_words {
+		let filter = match &config.stop_words {
+			Some(StopWords::Language(language)) => StopWordFilter::new(*language),
+			Some(StopWords::List(words)) => Some(StopWordFilter::remove(words.iter().map(|w| w.to_lowercase()))),
+			None => None,
+		};
+		if let Some(filter) = filter {
+			builder = builder.filter_dynamic(filter);
+		}
+	}
+	if let (true, Some(language)) = (stem, config.stemmer) {
+		builder = builder.filter_dynamic(Stemmer::new(language));
+	}
+	builder.build()
+}
+
 pub fn get_statistics(schema: &SearchEngineSchema, index: &tantivy::Index, index_dir: &str) -> tantivy::Result<IndexStatistics> {
 	let reader = index.reader_builder().try_into().unwrap();
 	let searcher = reader.searcher();
-	let domains_path = index_dir.to_string() + "/domains.txt";
+	let manifest = IndexManifest::open(index_dir)?;
 
-	let creation_time = if let Ok(creation_time) = fs::metadata(&domains_path)?.created() {
-		let date_time: DateTime<Utc> = creation_time.into();
-		date_time.to_rfc2822()
-	} else {
-		"Unknown".to_string()
-	};
+	let creation_time = format_time(&manifest.build_time, |t| t.to_rfc2822());
 
 	let size = get_size(index_dir)?;
 
 	let all_query = AllQuery {};
 	let page_count = all_query.count(&searcher)?.try_into().unwrap();
 
-	let domains = fs::read_to_string(&domains_path)?;
-	let domain_page_counts = domains.lines().map(|d| {
-		let term = Term::from_field_text(schema.domain, d);
+	// Count pages in the index itself, since legacy manifests have no counts
+	let domains = manifest.domains.iter().map(|d| {
+		let term = Term::from_field_text(schema.domain, &d.name);
 		let query = TermQuery::new(term, IndexRecordOption::Basic);
 		let count = query.count(&searcher).unwrap_or(0);
-		(d.to_string(), count.try_into().unwrap())
+		DomainStatistics {
+			name: d.name.clone(),
+			page_count: count.try_into().unwrap(),
+			crawl_date: format_time(&d.crawl_end, |t| t.format("%Y-%m-%d").to_string()),
+		}
 	}).collect::<Vec<_>>();
 
 	Ok(IndexStatistics {
 		creation_time,
 		size,
 		page_count,
-		domain_page_counts,
+		schema_version: manifest.schema_version,
+		indexer_version: manifest.indexer_version,
+		domains,
 	})
 }
 
+fn format_time(time: &Option<String>, format: impl Fn(DateTime<Utc>) -> String) -> String {
+	time.as_ref()
+		.and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+		.map_or("Unknown".to_string(), |t| format(t.into()))
+}
+
 pub fn get_size(index_dir: &str) -> tantivy::Result<u64> {
 	let mut total_size = 0u64;
 	for entry in fs::read_dir(index_dir)? {
@@ -211,7 +469,7 @@ impl TokenStream for CodeTokenStream<'_> {
 			if !c.is_ascii_whitespace() {
 				self.token.offset_from = offset_from;
 				self.token.offset_to = offset_from + 1;
-				self.token.text.push_str(&c.to_string());
+				self.token.text.push(c);
 				return true;
 			}
 	        }
@@ -0,0 +1,90 @@
+use std::fs::File;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use crate::compression::{self, Compression, Position};
+use crate::webpage::Webpage;
+
+// Compressed files are written in frames of about this size, so that reading
+// one page only needs part of the file to be decompressed
+const FRAME_SIZE: usize = 64 * 1024;
+
+// One line of a JSON Lines file with the pages of a domain, which can be
+// compressed with gzip or zstd
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct PageLine {
+	#[serde(flatten)]
+	pub webpage: Webpage,
+
+	// HTML of the page as it was received, if it was saved
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub html: Option<String>,
+}
+
+// Only the URL of a line, to list the pages without keeping them in memory
+#[derive(serde::Deserialize)]
+struct UrlLine {
+	url: String,
+}
+
+// Writes lines to a new file, compressing them in frames if the file name
+// ends with .gz or .zst
+pub struct JsonlWriter {
+	file: File,
+	compression: Compression,
+	frame: Vec<u8>,
+}
+
+// Calls f with the position, length and URL of each line, or the error if
+// the line can't be parsed, e.g. if it was cut off
+pub fn read_urls(path: &Path, mut f: impl FnMut(Position, u64, serde_json::Result<String>) -> io::Result<()>) -> io::Result<()> {
+	compression::read_frames(path, |frame| {
+		let mut line = Vec::new();
+		loop {
+			let position = frame.position()?;
+			line.clear();
+			if frame.read_until(b'\n', &mut line)? == 0 {
+				return Ok(())
+			}
+			if line.trim_ascii().is_empty() {
+				continue
+			}
+			let url = serde_json::from_slice::<UrlLine>(&line).map(|l| l.url);
+			f(position, line.len() as u64, url)?;
+		}
+	})
+}
+
+// Reads one line found by read_urls
+pub fn read_page_at(path: &Path, position: Position) -> io::Result<PageLine> {
+	let mut line = Vec::new();
+	compression::open_at(path, position)?.read_until(b'\n', &mut line)?;
+	Ok(serde_json::from_slice(&line)?)
+}
+
+impl JsonlWriter {
+	pub fn create(path: &Path) -> io::Result<JsonlWriter> {
+		Ok(JsonlWriter {file: File::create(path)?, compression: Compression::from_path(path), frame: Vec::new()})
+	}
+
+	pub fn write(&mut self, line: &PageLine) -> io::Result<()> {
+		serde_json::to_writer(&mut self.frame, line)?;
+		self.frame.push(b'\n');
+		if self.frame.len() >= FRAME_SIZE {
+			self.write_frame()?;
+		}
+		Ok(())
+	}
+
+	pub fn finish(mut self) -> io::Result<()> {
+		self.write_frame()?;
+		self.file.flush()
+	}
+
+	fn write_frame(&mut self) -> io::Result<()> {
+		if !self.frame.is_empty() {
+			self.file.write_all(&self.compression.compress(&self.frame)?)?;
+			self.frame.clear();
+		}
+		Ok(())
+	}
+}
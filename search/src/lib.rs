@@ -1,2 +1,16 @@
 pub mod search;
 pub mod index;
+pub mod suggest;
+pub mod synonyms;
+pub mod page_rank;
+pub mod urls;
+pub mod fingerprint;
+pub mod versions;
+pub mod webpage;
+pub mod extract;
+pub mod robots;
+pub mod crawl_state;
+pub mod sitemap;
+pub mod warc;
+pub mod compression;
+pub mod jsonl;
@@ -0,0 +1,201 @@
+// Parameters for computing page ranks, which are recorded in the index
+// manifest
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct PageRankOptions {
+	// Probability of following a link instead of jumping to a random page
+	pub damping: f64,
+
+	pub max_iterations: usize,
+
+	// Iteration stops once the ranks change by less than this in total
+	pub tolerance: f64,
+}
+
+impl Default for PageRankOptions {
+	fn default() -> PageRankOptions {
+		PageRankOptions {
+			damping: 0.85,
+			max_iterations: 100,
+			tolerance: 1e-9,
+		}
+	}
+}
+
+// The link graph as a sparse matrix in compressed row form, where row i lists
+// the pages linking to page i
+struct LinkMatrix {
+	row_starts: Vec<usize>,
+	sources: Vec<usize>,
+
+	// Number of distinct pages each page links to
+	outbound_counts: Vec<usize>,
+}
+
+impl LinkMatrix {
+	fn new(links: &[Vec<usize>]) -> LinkMatrix {
+		let page_count = links.len();
+		let mut outbound_counts = vec![0; page_count];
+		let mut inbound_counts = vec![0; page_count];
+		let mut edges = Vec::new();
+		for (i, targets) in links.iter().enumerate() {
+			let mut targets: Vec<usize> = targets.iter().copied().filter(|t| *t != i).collect();
+			targets.sort_unstable();
+			targets.dedup();
+			outbound_counts[i] = targets.len();
+			for t in targets {
+				inbound_counts[t] += 1;
+				edges.push((t, i));
+			}
+		}
+
+		let mut row_starts = Vec::with_capacity(page_count + 1);
+		row_starts.push(0);
+		for count in &inbound_counts {
+			row_starts.push(row_starts.last().unwrap() + count);
+		}
+		let mut next = row_starts.clone();
+		let mut sources = vec![0; edges.len()];
+		for (target, source) in edges {
+			sources[next[target]] = source;
+			next[target] += 1;
+		}
+
+		LinkMatrix {row_starts, sources, outbound_counts}
+	}
+
+	fn inbound(&self, page: usize) -> &[usize] {
+		&self.sources[self.row_starts[page]..self.row_starts[page + 1]]
+	}
+}
+
+// Takes the pages that each page links to, as indices into the same list, and
+// returns the rank of each page. Ranks add up to 1.
+//
+// Links from a page to itself and repeated links to the same page are ignored.
+// Pages without any links (dangling pages) are treated as linking to every
+// page, so their rank is spread evenly instead of being lost.
+pub fn get_page_ranks(links: &[Vec<usize>], options: &PageRankOptions) -> Vec<f64> {
+	let page_count = links.len();
+	if page_count == 0 {
+		return Vec::new()
+	}
+
+	let matrix = LinkMatrix::new(links);
+	let damp = options.damping;
+	let inv_page_count = 1.0 / page_count as f64;
+	let mut page_ranks = vec![inv_page_count; page_count];
+	let mut contributions = vec![0.0; page_count];
+	for _ in 0..options.max_iterations {
+		// Rank passed along each link of a page
+		let mut dangling_rank = 0.0;
+		for (i, rank) in page_ranks.iter().enumerate() {
+			match matrix.outbound_counts[i] {
+				0 => {
+					dangling_rank += rank;
+					contributions[i] = 0.0;
+				},
+				count => contributions[i] = rank / count as f64,
+			}
+		}
+		let base = (1.0 - damp) * inv_page_count + damp * dangling_rank * inv_page_count;
+
+		let new_page_ranks: Vec<f64> = (0..page_count).map(|i| {
+			let sum: f64 = matrix.inbound(i).iter().map(|s| contributions[*s]).sum();
+			base + damp * sum
+		}).collect();
+
+		let change: f64 = new_page_ranks.iter().zip(&page_ranks).map(|(a, b)| (a - b).abs()).sum();
+		page_ranks = new_page_ranks;
+		if change < options.tolerance {
+			break
+		}
+	}
+
+	page_ranks
+}
+
+// Scales ranks so that the best page gets u64::MAX, since ranks adding up to 1
+// would otherwise be tiny for large sites
+pub fn scale_page_ranks(page_ranks: &[f64]) -> Vec<u64> {
+	let max = page_ranks.iter().copied().fold(0.0, f64::max);
+	if max <= 0.0 {
+		return vec![0; page_ranks.len()]
+	}
+	page_ranks.iter().map(|rank| {
+		// Casts from f64 saturate, so the best page maps exactly to u64::MAX.
+		// 0 is left for pages without a rank.
+		let scaled = rank / max * u64::MAX as f64;
+		(scaled as u64).max(1)
+	}).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn assert_ranks(links: &[Vec<usize>], expected: &[f64]) {
+		let page_ranks = get_page_ranks(links, &PageRankOptions::default());
+		assert_eq!(page_ranks.len(), expected.len());
+		for (rank, expected) in page_ranks.iter().zip(expected) {
+			assert!((rank - expected).abs() < 1e-6, "{:?} != {:?}", page_ranks, expected);
+		}
+		assert!((page_ranks.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+	}
+
+	#[test]
+	fn empty_graph() {
+		assert!(get_page_ranks(&[], &PageRankOptions::default()).is_empty());
+		assert!(scale_page_ranks(&[]).is_empty());
+	}
+
+	#[test]
+	fn cycle() {
+		assert_ranks(&[vec![1], vec![2], vec![0]], &[1.0 / 3.0; 3]);
+	}
+
+	#[test]
+	fn three_pages() {
+		assert_ranks(&[vec![1, 2], vec![2], vec![0]], &[0.3877897, 0.2148106, 0.3973997]);
+	}
+
+	#[test]
+	fn star() {
+		assert_ranks(&[vec![1, 2, 3], vec![0], vec![0], vec![0]], &[0.4797297, 0.1734234, 0.1734234, 0.1734234]);
+	}
+
+	#[test]
+	fn dangling_page() {
+		assert_ranks(&[vec![1], vec![2], vec![]], &[0.1844168, 0.3411710, 0.4744122]);
+	}
+
+	#[test]
+	fn no_links() {
+		assert_ranks(&[vec![], vec![], vec![], vec![]], &[0.25; 4]);
+	}
+
+	#[test]
+	fn self_and_duplicate_links_are_ignored() {
+		let links = [vec![0, 1, 1, 2], vec![1, 2, 2], vec![0, 0]];
+		assert_ranks(&links, &[0.3877897, 0.2148106, 0.3973997]);
+	}
+
+	#[test]
+	fn stops_at_tolerance() {
+		let links = [vec![1, 2], vec![2], vec![0]];
+		let options = PageRankOptions {tolerance: 1e-3, ..PageRankOptions::default()};
+		let page_ranks = get_page_ranks(&links, &options);
+		let converged = get_page_ranks(&links, &PageRankOptions::default());
+		let error: f64 = page_ranks.iter().zip(&converged).map(|(a, b)| (a - b).abs()).sum();
+		assert!(error > 1e-9 && error < 1e-2);
+	}
+
+	#[test]
+	fn scaling_does_not_saturate() {
+		let scaled = scale_page_ranks(&[0.5, 0.25, 0.25, 0.0]);
+		assert_eq!(scaled[0], u64::MAX);
+		assert!(scaled[1] > u64::MAX / 2 - 4096 && scaled[1] < u64::MAX / 2 + 4096);
+		assert_eq!(scaled[1], scaled[2]);
+		assert_eq!(scaled[3], 1);
+	}
+}
@@ -0,0 +1,219 @@
+// Rules from a robots.txt file that apply to one user agent
+#[derive(Default)]
+pub struct Robots {
+	// Path patterns, and whether they allow or disallow crawling
+	rules: Vec<(bool, String)>,
+
+	// Seconds to wait between requests
+	pub crawl_delay: Option<f64>,
+
+	// Sitemaps apply to every user agent
+	pub sitemaps: Vec<String>,
+}
+
+impl Robots {
+	// Parses robots.txt, keeping the rules of the most specific group of user
+	// agents that matches, or of the "*" group if none do
+	pub fn parse(text: &str, user_agent: &str) -> Robots {
+		let product = get_product_token(user_agent);
+		let mut robots = Robots::default();
+		let mut default_robots = Robots::default();
+		let mut found_group = false;
+
+		// State of the current group: whether it applies to us or to every
+		// agent, and whether it has any rules yet (a new User-agent line after
+		// rules starts a new group)
+		let mut matches = false;
+		let mut matches_any = false;
+		let mut in_rules = false;
+
+		for line in text.lines() {
+			let line = line.split('#').next().unwrap().trim();
+			let Some((key, value)) = line.split_once(':') else {
+				continue
+			};
+			let key = key.trim().to_ascii_lowercase();
+			let value = value.trim();
+			match key.as_str() {
+				"user-agent" => {
+					if in_rules {
+						matches = false;
+						matches_any = false;
+						in_rules = false;
+					}
+					let agent = value.to_ascii_lowercase();
+					if agent == "*" {
+						matches_any = true;
+					} else if !agent.is_empty() && product.contains(&agent) {
+						matches = true;
+						found_group = true;
+					}
+				},
+				"allow" | "disallow" | "crawl-delay" => {
+					in_rules = true;
+					let target = if matches {
+						&mut robots
+					} else if matches_any {
+						&mut default_robots
+					} else {
+						continue
+					};
+					match key.as_str() {
+						// "nan" and "inf" parse as numbers but aren't delays
+						"crawl-delay" => {
+							target.crawl_delay = value.parse().ok().filter(|d: &f64| d.is_finite()).or(target.crawl_delay)
+						},
+						// An empty Disallow allows everything
+						_ if value.is_empty() => {},
+						_ => target.rules.push((key == "allow", value.to_string())),
+					}
+				},
+				"sitemap" => {
+					// The value is a URL, which has its own colon
+					if let Some(url) = line.get(line.find(':').unwrap() + 1..) {
+						robots.sitemaps.push(url.trim().to_string());
+					}
+				},
+				_ => {},
+			}
+		}
+
+		let sitemaps = std::mem::take(&mut robots.sitemaps);
+		let mut robots = if found_group { robots } else { default_robots };
+		robots.sitemaps = sitemaps;
+		robots
+	}
+
+	// Checks a path (with its query string) against the rules. The longest
+	// matching pattern wins, and Allow wins when patterns are the same length.
+	pub fn is_allowed(&self, path: &str) -> bool {
+		let mut best: Option<(usize, bool)> = None;
+		for (allow, pattern) in &self.rules {
+			if !matches_pattern(pattern, path) {
+				continue
+			}
+			let length = pattern.len();
+			match best {
+				Some((l, a)) if l > length || (l == length && a) => {},
+				_ => best = Some((length, *allow)),
+			}
+		}
+		best.is_none_or(|(_, allow)| allow)
+	}
+}
+
+// "Mozilla/5.0 (compatible; Examplebot/1.0)" isn't handled, only agents that
+// start with their name, e.g. "mini_search_engine/0.1 (+https://...)"
+fn get_product_token(user_agent: &str) -> String {
+	user_agent.split(['/', ' ']).next().unwrap_or("").to_ascii_lowercase()
+}
+
+// Patterns match the start of the path, "*" matches any characters and a
+// trailing "$" matches the end of the path
+fn matches_pattern(pattern: &str, path: &str) -> bool {
+	let (pattern, anchored) = match pattern.strip_suffix('$') {
+		Some(p) => (p, true),
+		None => (pattern, false),
+	};
+	let mut parts = pattern.split('*');
+	let first = parts.next().unwrap();
+	let Some(mut rest) = path.strip_prefix(first) else {
+		return false
+	};
+	let parts: Vec<&str> = parts.collect();
+	for (i, part) in parts.iter().enumerate() {
+		let is_last = i + 1 == parts.len();
+		if is_last && anchored {
+			return rest.ends_with(part)
+		}
+		match rest.find(part) {
+			Some(j) => rest = &rest[j + part.len()..],
+			None => return false,
+		}
+	}
+	!anchored || rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn ignores_crawl_delays_that_are_not_numbers() {
+		for delay in ["nan", "NaN", "inf", "-inf", "soon"] {
+			let robots = Robots::parse(&format!("User-agent: *\nCrawl-delay: {}\n", delay), "bot/1.0");
+			assert_eq!(robots.crawl_delay, None, "{}", delay);
+		}
+		let robots = Robots::parse("User-agent: *\nCrawl-delay: 2.5\nCrawl-delay: nan\n", "bot/1.0");
+		assert_eq!(robots.crawl_delay, Some(2.5));
+	}
+
+	#[test]
+	fn matches_wildcards_and_end_anchors() {
+		assert!(matches_pattern("/docs", "/docs/index.html"));
+		assert!(!matches_pattern("/docs", "/api/docs"));
+		assert!(matches_pattern("/*.php", "/a/b.php?x=1"));
+		assert!(matches_pattern("/a*b*c", "/a-b-c"));
+		assert!(!matches_pattern("/a*b*c", "/a-c-b"));
+		assert!(matches_pattern("/*.php$", "/a/b.php"));
+		assert!(!matches_pattern("/*.php$", "/a/b.php?x=1"));
+		assert!(matches_pattern("/$", "/"));
+		assert!(!matches_pattern("/$", "/index.html"));
+		assert!(matches_pattern("/private*$", "/private/a"));
+		assert!(matches_pattern("*", "/anything"));
+	}
+
+	#[test]
+	fn longest_match_wins_and_allow_wins_ties() {
+		let robots = Robots::parse("User-agent: *\n\
+			Disallow: /docs/\n\
+			Allow: /docs/public/\n\
+			Disallow: /docs/public/drafts\n\
+			Allow: /tie\n\
+			Disallow: /tie\n\
+			Disallow: /*.pdf$\n\
+			Disallow:\n", "bot/1.0");
+		assert!(!robots.is_allowed("/docs/a.html"));
+		assert!(robots.is_allowed("/docs/public/a.html"));
+		assert!(!robots.is_allowed("/docs/public/drafts/a.html"));
+		assert!(robots.is_allowed("/tie"));
+		assert!(!robots.is_allowed("/a.pdf"));
+		assert!(robots.is_allowed("/a.pdf?page=2"));
+		assert!(robots.is_allowed("/other"));
+	}
+
+	#[test]
+	fn uses_the_group_for_the_user_agent() {
+		let text = "User-agent: *\n\
+			Disallow: /\n\
+			Crawl-delay: 10\n\
+			\n\
+			User-agent: otherbot\n\
+			User-agent: Bot\n\
+			Disallow: /private\n\
+			Crawl-delay: 1\n\
+			\n\
+			User-agent: someone-else\n\
+			Allow: /private\n\
+			\n\
+			Sitemap: https://example.com/sitemap.xml\n";
+
+		// Agent names are compared with the product token, ignoring case
+		let robots = Robots::parse(text, "bot/1.0 (+https://example.com)");
+		assert!(robots.is_allowed("/docs"));
+		assert!(!robots.is_allowed("/private"));
+		assert_eq!(robots.crawl_delay, Some(1.0));
+		assert_eq!(robots.sitemaps, ["https://example.com/sitemap.xml"]);
+
+		// Other agents get the "*" group
+		let robots = Robots::parse(text, "crawler/2.0");
+		assert!(!robots.is_allowed("/docs"));
+		assert_eq!(robots.crawl_delay, Some(10.0));
+		assert_eq!(robots.sitemaps, ["https://example.com/sitemap.xml"]);
+
+		// Without a "*" group, everything is allowed
+		let robots = Robots::parse("User-agent: someone-else\nDisallow: /\n", "bot/1.0");
+		assert!(robots.is_allowed("/docs"));
+		assert_eq!(robots.crawl_delay, None);
+	}
+}
@@ -1,15 +1,22 @@
-use tantivy::{SegmentReader, DocId, Score};
+use tantivy::{SegmentReader, Searcher, DocAddress, DocId, Score};
 use tantivy::schema::{Term, Value, Field, IndexRecordOption};
 use tantivy::tokenizer::TextAnalyzer;
-use tantivy::query::{Occur, Query, TermQuery, BoostQuery, PhraseQuery, BooleanQuery};
+use tantivy::query::{Occur, Query, TermQuery, BoostQuery, PhraseQuery, PhrasePrefixQuery, TermSetQuery, BooleanQuery, RegexQuery};
 use tantivy::collector::TopDocs;
 use crate::index::SearchEngineSchema;
+use crate::synonyms::Synonyms;
+use crate::fingerprint;
+use crate::versions;
 
 struct UserQuery {
 	domain: Option<String>,
+	version: Option<String>,
 	text_terms: String,
+	exact_terms: String,
 	text_phrases: Vec<String>,
 	code_phrases: Vec<String>,
+	fuzzy_terms: Vec<(String, u8)>,
+	prefix_terms: Vec<String>,
 }
 
 pub enum SearchResults {
@@ -28,6 +35,29 @@ pub struct Entry {
 	pub title: String,
 	pub url: String,
 	pub excerpt: String,
+
+	// Title of the page, if the result is one section of it
+	pub parent_title: Option<String>,
+
+	// Heading of the page which best matches the search
+	pub deep_link: Option<DeepLink>,
+
+	// Lower ranked results which are nearly the same page, e.g. the same docs
+	// for another version
+	pub similar: Vec<SimilarPage>,
+}
+
+pub struct DeepLink {
+	pub url: String,
+
+	// Headings the linked heading is under, outermost first, followed by the
+	// linked heading itself
+	pub breadcrumb: Vec<String>,
+}
+
+pub struct SimilarPage {
+	pub title: String,
+	pub url: String,
 }
 
 // Limits on the size of the query to ensure that no searches can be made which
@@ -37,7 +67,43 @@ const MAX_PHRASE_TOKENS: usize = 32;
 const MAX_PHRASES: usize = 16;
 const MAX_TERMS: usize = 128;
 
-pub fn search(schema: &SearchEngineSchema, index: &tantivy::Index, query_string: &str) -> Option<SearchResults> {
+// Fuzzy and prefix terms are expanded into many terms from the index, so they
+// are limited separately. A single fuzzy term can contain many tokens, so the
+// tokens of all fuzzy terms are limited too.
+const MAX_FUZZY_TERMS: usize = 4;
+const MAX_FUZZY_TOKENS: usize = 8;
+const MAX_FUZZY_EXPANSIONS: usize = 50;
+const MAX_PREFIX_TERMS: usize = 4;
+const MAX_PREFIX_EXPANSIONS: u32 = 50;
+const MIN_PREFIX_LENGTH: usize = 2;
+
+// Synonyms of search terms are scored lower than the terms themselves
+const SYNONYM_BOOST: f32 = 0.5;
+const MAX_SYNONYM_VARIANTS: usize = 8;
+
+// Number of results shown. Extra results are retrieved so that there are still
+// enough left after collapsing near-duplicates.
+const MAX_RESULTS: usize = 10;
+const MAX_CANDIDATES: usize = 4 * MAX_RESULTS;
+
+// How much the page rank computed over all domains counts compared to the
+// page rank within the page's own domain
+const GLOBAL_PAGE_RANK_WEIGHT: f32 = 0.5;
+
+// Score multiplier for each newer version of the docs a page is from, unless
+// the query asks for a version
+const OLD_VERSION_WEIGHT: f32 = 0.5;
+const MAX_VERSION_AGE: u64 = 4;
+
+// Short words have too many neighbors within a given edit distance, so the
+// distance is reduced for them
+const MIN_FUZZY_LENGTH: [usize; 3] = [0, 3, 6];
+
+// Number of leading characters which a fuzzy term must match exactly, so that
+// only the terms starting with them are compared with it
+const FUZZY_PREFIX_LENGTH: usize = 1;
+
+pub fn search(schema: &SearchEngineSchema, index: &tantivy::Index, synonyms: &Synonyms, query_string: &str) -> Option<SearchResults> {
 	if query_string.len() > MAX_QUERY_STRING_LENGTH {
 		return Some(SearchResults::Error(format!("Search query is too long! (max. {} characters)", MAX_QUERY_STRING_LENGTH)))
 	}
@@ -49,9 +115,19 @@ pub fn search(schema: &SearchEngineSchema, index: &tantivy::Index, query_string:
 
 	let mut analyzer = index.tokenizers().get("text")?;
 	let mut code_analyzer = index.tokenizers().get("code")?;
+	let mut prefix_analyzer = index.tokenizers().get("text_prefix")?;
+	let mut term_analyzer = index.tokenizers().get("text_without_stop_words")?;
+
+	let text_fields = &[
+		(schema.headings, 8.0),
+		(schema.text, 1.0),
+	];
 
-	let text_fields = &vec![
+	// Anchor text only helps single terms, since phrases have to match in
+	// every field they're searched in
+	let term_fields = &[
 		(schema.headings, 8.0),
+		(schema.anchors, 4.0),
 		(schema.text, 1.0),
 	];
 
@@ -60,56 +136,164 @@ pub fn search(schema: &SearchEngineSchema, index: &tantivy::Index, query_string:
 	let mut excerpt_highlight_terms = Vec::new();
 
 	// Create tantivy queries from user query
-	let text_phrase_queries = get_phrase_queries(&user_query.text_phrases, &mut analyzer, &text_fields, &mut excerpt_highlight_terms);
-	let code_phrase_queries = get_phrase_queries(&user_query.code_phrases, &mut code_analyzer, &vec![(schema.code, 1.0)], &mut Vec::new());
+	let text_phrase_queries = get_phrase_queries(&user_query.text_phrases, &mut analyzer, text_fields, Some(synonyms), &mut excerpt_highlight_terms);
+	let code_phrase_queries = get_phrase_queries(&user_query.code_phrases, &mut code_analyzer, &[(schema.code, 1.0)], None, &mut Vec::new());
 	let domain_query = user_query.domain.map(|str| -> Box<dyn Query> {
 		let term = Term::from_field_text(schema.domain, str.as_str());
 		Box::new(TermQuery::new(term, IndexRecordOption::Basic))
 	});
-	let term_queries = get_term_queries(&user_query.text_terms, &mut analyzer, &text_fields, &mut excerpt_highlight_terms);
+	// A version which doesn't give a valid pattern is left out instead of failing
+	// the whole search
+	let version_query = user_query.version
+		.and_then(|version| RegexQuery::from_pattern(&versions::get_version_pattern(&version), schema.version).ok())
+		.map(|q| Box::new(q) as Box<dyn Query>);
+	let prefer_newest = version_query.is_none();
+	let mut term_queries = get_term_queries(&user_query.text_terms, &mut term_analyzer, term_fields, Some(synonyms), &mut excerpt_highlight_terms);
+	// Terms forced with + are searched for even if they are stop words
+	term_queries.extend(get_term_queries(&user_query.exact_terms, &mut analyzer, term_fields, None, &mut excerpt_highlight_terms));
+	let fuzzy_queries = get_fuzzy_queries(&searcher, &user_query.fuzzy_terms, &mut analyzer, term_fields, &mut excerpt_highlight_terms).ok()?;
+	let prefix_queries = get_prefix_queries(&user_query.prefix_terms, &mut analyzer, &mut prefix_analyzer, term_fields, &mut excerpt_highlight_terms);
 
-	if text_phrase_queries.is_empty() && code_phrase_queries.is_empty() && domain_query.is_none() && term_queries.is_empty() {
+	// Stop words match almost every page, so they only add to the score of
+	// pages matching the rest of the query, unless the query has nothing else
+	// in it
+	let stop_words = get_stop_words(&user_query.text_terms, &mut analyzer, &mut term_analyzer);
+	let mut stop_word_queries = get_term_queries(&stop_words, &mut analyzer, term_fields, None, &mut Vec::new());
+	if text_phrase_queries.is_empty() && code_phrase_queries.is_empty() && domain_query.is_none() && version_query.is_none()
+		&& term_queries.is_empty() && fuzzy_queries.is_empty() && prefix_queries.is_empty() {
+		term_queries = get_term_queries(&user_query.text_terms, &mut analyzer, term_fields, Some(synonyms), &mut excerpt_highlight_terms);
+		stop_word_queries.clear();
+	}
+
+	if text_phrase_queries.is_empty() && code_phrase_queries.is_empty() && domain_query.is_none() && version_query.is_none()
+		&& term_queries.is_empty() && fuzzy_queries.is_empty() && prefix_queries.is_empty() {
 		// Empty string represents that no search was made because the query was empty
 		return Some(SearchResults::Error("".to_string()));
 	}
 
 	// Construct full query and get top docs
 	let queries: Vec<_> = text_phrase_queries.into_iter()
-		.chain(code_phrase_queries.into_iter())
-		.chain(domain_query.into_iter())
+		.chain(code_phrase_queries)
+		.chain(domain_query)
+		.chain(version_query)
 		.map(|q| (Occur::Must, q))
 		.chain(term_queries.into_iter().map(|q| (Occur::Should, q)))
+		.chain(fuzzy_queries.into_iter().map(|q| (Occur::Should, q)))
+		.chain(prefix_queries.into_iter().map(|q| (Occur::Should, q)))
 		.collect();
-	let boolean_query = BooleanQuery::new(queries);
-	let top_docs = searcher.search(&boolean_query, &TopDocs::with_limit(10).tweak_score(move |segment_reader: &SegmentReader| {
+	let mut boolean_query = BooleanQuery::new(queries);
+	if !stop_word_queries.is_empty() {
+		let queries = std::iter::once((Occur::Must, Box::new(boolean_query) as Box<dyn Query>))
+			.chain(stop_word_queries.into_iter().map(|q| (Occur::Should, q)))
+			.collect();
+		boolean_query = BooleanQuery::new(queries);
+	}
+	let top_docs = searcher.search(&boolean_query, &TopDocs::with_limit(MAX_CANDIDATES).tweak_score(move |segment_reader: &SegmentReader| {
 		let reader = segment_reader.fast_fields().u64("page_rank").unwrap().first_or_default_col(0);
+		let global_reader = segment_reader.fast_fields().u64("global_page_rank").unwrap().first_or_default_col(0);
+		let version_age_reader = segment_reader.fast_fields().u64("version_age").unwrap().first_or_default_col(0);
 		move |doc: DocId, original_score: Score| {
 			let page_rank: u64 = reader.get_val(doc);
-			let inv_u64_max = 1.0 / std::u64::MAX as f32;
-			original_score * (page_rank as f32 * inv_u64_max).powf(0.15)
+			let global_page_rank: u64 = match global_reader.get_val(doc) {
+				// Not computed for this index
+				0 => page_rank,
+				x => x,
+			};
+			let inv_u64_max = 1.0 / u64::MAX as f32;
+			let page_rank = page_rank as f32 * inv_u64_max;
+			let global_page_rank = global_page_rank as f32 * inv_u64_max;
+			let w = GLOBAL_PAGE_RANK_WEIGHT;
+			let score = original_score * (page_rank.powf(1.0 - w) * global_page_rank.powf(w)).powf(0.15);
+			if prefer_newest {
+				let version_age = version_age_reader.get_val(doc).min(MAX_VERSION_AGE);
+				score * OLD_VERSION_WEIGHT.powi(version_age as i32)
+			} else {
+				score
+			}
 		}
 	})).ok()?;
 
+	// Group near-duplicates under the best ranked one
+	let mut groups: Vec<(u64, DocAddress, Vec<DocAddress>)> = Vec::new();
+	for (_, address) in top_docs {
+		let fingerprints = searcher.segment_reader(address.segment_ord).fast_fields().u64("fingerprint").ok()?.first_or_default_col(0);
+		let fingerprint = fingerprints.get_val(address.doc_id);
+		match groups.iter().position(|(f, _, _)| fingerprint::is_similar(*f, fingerprint)) {
+			Some(i) => groups[i].2.push(address),
+			None if groups.len() < MAX_RESULTS => groups.push((fingerprint, address, Vec::new())),
+			None => {},
+		}
+	}
+
 	// Get info for user from resulting documents
 	let mut results = Vec::new();
-	for (_, address) in top_docs {
+	for (_, address, similar) in groups {
 		let retrieved_doc: tantivy::TantivyDocument = searcher.doc(address).ok()?;
 		let text = retrieved_doc.get_first(schema.text).unwrap().as_str().unwrap();
-	results.push(Entry {
+		let url = retrieved_doc.get_first(schema.url).unwrap().as_str().unwrap().to_string();
+		let sections: Vec<&str> = retrieved_doc.get_all(schema.sections).filter_map(|v| v.as_str()).collect();
+		let deep_link = get_deep_link(&url, &sections, &mut analyzer, &excerpt_highlight_terms);
+		let similar = similar.into_iter().map(|address| {
+			let doc: tantivy::TantivyDocument = searcher.doc(address).ok()?;
+			Some(SimilarPage {
+				title: doc.get_first(schema.title).unwrap().as_str().unwrap().to_string(),
+				url: doc.get_first(schema.url).unwrap().as_str().unwrap().to_string(),
+			})
+		}).collect::<Option<Vec<_>>>()?;
+		results.push(Entry {
 			title: retrieved_doc.get_first(schema.title).unwrap().as_str().unwrap().to_string(),
-			url: retrieved_doc.get_first(schema.url).unwrap().as_str().unwrap().to_string(),
+			url,
 			excerpt: get_excerpt(text, &excerpt_highlight_terms),
+			parent_title: retrieved_doc.get_first(schema.parent_title).and_then(|v| v.as_str()).map(|s| s.to_string()),
+			deep_link,
+			similar,
 		});
 	}
 
 	Some(SearchResults::Entries(results))
 }
 
+// Links to the heading which contains the most search terms, if any. Sections
+// are stored as "level\tid\theading", see get_schema.
+fn get_deep_link(url: &str, sections: &[&str], analyzer: &mut TextAnalyzer, terms: &[String]) -> Option<DeepLink> {
+	let sections: Vec<(u8, &str, &str)> = sections.iter().filter_map(|s| {
+		let mut parts = s.splitn(3, '\t');
+		Some((parts.next()?.parse().ok()?, parts.next()?, parts.next()?))
+	}).collect();
+
+	let mut best = None;
+	let mut best_count = 0;
+	for (i, (_, _, heading)) in sections.iter().enumerate() {
+		let mut matched = Vec::new();
+		let mut token_stream = analyzer.token_stream(heading);
+		while let Some(token) = token_stream.next() {
+			if terms.contains(&token.text) && !matched.contains(&token.text) {
+				matched.push(token.text.clone());
+			}
+		}
+		if matched.len() > best_count {
+			best = Some(i);
+			best_count = matched.len();
+		}
+	}
+
+	let (mut level, id, heading) = sections[best?];
+	let mut breadcrumb = vec![heading.to_string()];
+	for (l, _, h) in sections[..best?].iter().rev() {
+		if *l < level {
+			breadcrumb.push(h.to_string());
+			level = *l;
+		}
+	}
+	breadcrumb.reverse();
+	Some(DeepLink {url: format!("{}#{}", url, id), breadcrumb})
+}
+
 // Very cheap way to get an excerpt from the text which matches the query.
 // Tokenizer takes way too long, and tantivy doesn't seem to have a way to
 // extract token data from the index once the tokens have already been indexed,
 // so we just do simple string matching.
-fn get_excerpt(text: &str, highlight_terms: &Vec<String>) -> String {
+fn get_excerpt(text: &str, highlight_terms: &[String]) -> String {
 	// Just find the first search term in the full text and use that as
 	// the excerpt.
 	let index = highlight_terms.iter().filter_map(|pattern| text.find(pattern)).next().unwrap_or(0);
@@ -139,7 +323,7 @@ fn next_char_boundary(s: &str, mut i: usize) -> usize {
 
 // Returns true if the given byte is the first byte of a character encoded in UTF-8
 fn is_char_boundary(b: u8) -> bool {
-	!(0x80 <= b && b < 0xc0)
+	!(0x80..0xc0).contains(&b)
 }
 
 fn escape_html(input: &str) -> String {
@@ -157,51 +341,78 @@ fn escape_html(input: &str) -> String {
 	res
 }
 
-fn get_phrase_queries(phrases: &Vec<String>, analyzer: &mut TextAnalyzer, fields: &Vec<(Field, f32)>, terms: &mut Vec<String>) -> Vec<Box<dyn Query>> {
+fn get_phrase_queries(phrases: &[String], analyzer: &mut TextAnalyzer, fields: &[(Field, f32)], synonyms: Option<&Synonyms>, terms: &mut Vec<String>) -> Vec<Box<dyn Query>> {
 	let mut queries: Vec<Box<dyn Query>> = Vec::new();
 	for p in phrases.iter().take(MAX_PHRASES) {
-		let mut token_stream = analyzer.token_stream(&p);
-		let mut vs = vec![Vec::new(); fields.len()];
+		let mut token_stream = analyzer.token_stream(p);
+		let mut tokens = Vec::new();
 		while let Some(token) = token_stream.next() {
-			terms.push(token.text.clone());
-			for (v, (f, _)) in vs.iter_mut().zip(fields) {
-				let term = Term::from_field_text(*f, token.text.as_str());
-				v.push(term);
-			}
-			if vs[0].len() >= MAX_PHRASE_TOKENS {
+			tokens.push(token.text.clone());
+			if tokens.len() >= MAX_PHRASE_TOKENS {
 				// Implicitly ignore any more tokens in phrase
 				break
 			}
 		}
-		debug_assert!(!vs.is_empty());
-		if vs[0].len() > 1 {
-			for (v, (_, b)) in vs.into_iter().zip(fields) {
-				let q = Box::new(PhraseQuery::new(v));
-				let q = Box::new(BoostQuery::new(q, *b));
-				queries.push(q);
-			}
+		if tokens.is_empty() {
 			continue
 		}
-		for (v, (_, b)) in vs.into_iter().zip(fields) {
-			queries.extend(v.into_iter().map(|term| -> Box<dyn Query> {
-				let q = Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs));
-				Box::new(BoostQuery::new(q, *b))
-			}));
+		let variants = get_synonym_variants(&tokens, synonyms);
+		terms.extend(tokens.iter().cloned());
+		for (f, b) in fields {
+			let q = Box::new(BoostQuery::new(get_tokens_query(*f, &tokens), *b));
+			if variants.is_empty() {
+				queries.push(q);
+				continue
+			}
+			// The phrase must match, but any one of its variants is enough
+			let mut qs: Vec<(Occur, Box<dyn Query>)> = vec![(Occur::Should, q)];
+			for v in &variants {
+				let q = Box::new(BoostQuery::new(get_tokens_query(*f, v), *b * SYNONYM_BOOST));
+				qs.push((Occur::Should, q));
+			}
+			queries.push(Box::new(BooleanQuery::new(qs)));
 		}
 	}
 	queries
 }
 
-fn get_term_queries(text_terms: &String, analyzer: &mut TextAnalyzer, fields: &Vec<(Field, f32)>, terms: &mut Vec<String>) -> Vec<Box<dyn Query>> {
+// Words of the search terms which are left out as stop words, as text to search
+// for with the analyzer which keeps them. The words are matched up by their
+// position, since they can only be compared before stemming.
+fn get_stop_words(text_terms: &str, analyzer: &mut TextAnalyzer, term_analyzer: &mut TextAnalyzer) -> String {
+	let mut kept = Vec::new();
+	let mut token_stream = term_analyzer.token_stream(text_terms);
+	while let Some(token) = token_stream.next() {
+		kept.push(token.offset_from);
+	}
+	let mut stop_words = String::new();
+	let mut token_stream = analyzer.token_stream(text_terms);
+	while let Some(token) = token_stream.next() {
+		if !kept.contains(&token.offset_from) {
+			stop_words.push_str(&text_terms[token.offset_from..token.offset_to]);
+			stop_words.push(' ');
+		}
+	}
+	stop_words
+}
+
+fn get_term_queries(text_terms: &str, analyzer: &mut TextAnalyzer, fields: &[(Field, f32)], synonyms: Option<&Synonyms>, terms: &mut Vec<String>) -> Vec<Box<dyn Query>> {
 	let mut res: Vec<Box<dyn Query>> = Vec::new();
 	let mut token_stream = analyzer.token_stream(text_terms);
 	while let Some(token) = token_stream.next() {
-		terms.push(token.text.clone());
+		let tokens = vec![token.text.clone()];
+		let variants = get_synonym_variants(&tokens, synonyms);
+		terms.extend(tokens);
+		terms.extend(variants.iter().flatten().cloned());
 		for (f, b) in fields {
 			let term = Term::from_field_text(*f, token.text.as_str());
 			let q = Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs));
 			let q = Box::new(BoostQuery::new(q, *b));
 			res.push(q);
+			for v in &variants {
+				let q = Box::new(BoostQuery::new(get_tokens_query(*f, v), *b * SYNONYM_BOOST));
+				res.push(q);
+			}
 		}
 		if res.len() >= MAX_TERMS {
 			// Implicitly ignore any more terms in search
@@ -211,15 +422,176 @@ fn get_term_queries(text_terms: &String, analyzer: &mut TextAnalyzer, fields: &V
 	res
 }
 
+// Alternative token sequences for a phrase, each with one token replaced by one
+// of its synonyms
+fn get_synonym_variants(tokens: &[String], synonyms: Option<&Synonyms>) -> Vec<Vec<String>> {
+	let mut variants = Vec::new();
+	let Some(synonyms) = synonyms else {
+		return variants
+	};
+	for (i, token) in tokens.iter().enumerate() {
+		for replacement in synonyms.get(token) {
+			if variants.len() >= MAX_SYNONYM_VARIANTS {
+				return variants
+			}
+			let mut variant = tokens[..i].to_vec();
+			variant.extend(replacement.iter().cloned());
+			variant.extend(tokens[i + 1..].iter().cloned());
+			variants.push(variant);
+		}
+	}
+	variants
+}
+
+// Matches a single token, or a sequence of tokens as a phrase
+fn get_tokens_query(field: Field, tokens: &[String]) -> Box<dyn Query> {
+	let mut terms: Vec<_> = tokens.iter().map(|t| Term::from_field_text(field, t.as_str())).collect();
+	if terms.len() == 1 {
+		return Box::new(TermQuery::new(terms.pop().unwrap(), IndexRecordOption::WithFreqs))
+	}
+	Box::new(PhraseQuery::new(terms))
+}
+
+// Fuzzy terms (`term~`, `term~2`) match the indexed terms closest to the stemmed
+// search term within the given edit distance
+fn get_fuzzy_queries(searcher: &Searcher, fuzzy_terms: &[(String, u8)], analyzer: &mut TextAnalyzer, fields: &[(Field, f32)], terms: &mut Vec<String>) -> tantivy::Result<Vec<Box<dyn Query>>> {
+	let mut res: Vec<Box<dyn Query>> = Vec::new();
+	let mut token_count = 0;
+	'terms: for (text, distance) in fuzzy_terms.iter().take(MAX_FUZZY_TERMS) {
+		let mut token_stream = analyzer.token_stream(text);
+		while let Some(token) = token_stream.next() {
+			if token_count >= MAX_FUZZY_TOKENS {
+				// Implicitly ignore any more tokens in search
+				break 'terms
+			}
+			token_count += 1;
+			let length = token.text.chars().count();
+			let distance = (0..=*distance).rev()
+				.find(|d| length >= MIN_FUZZY_LENGTH[*d as usize])
+				.unwrap_or(0);
+			terms.push(token.text.clone());
+			for (f, b) in fields {
+				let expansions = get_fuzzy_expansions(searcher, *f, &token.text, distance as usize)?;
+				let q = Box::new(TermSetQuery::new(expansions));
+				let q = Box::new(BoostQuery::new(q, *b));
+				res.push(q);
+			}
+		}
+	}
+	Ok(res)
+}
+
+// Indexed terms of a field within the edit distance of a token, closest first.
+// Only the terms which start with the same characters as the token are looked
+// at, and at most MAX_FUZZY_EXPANSIONS of them are kept.
+fn get_fuzzy_expansions(searcher: &Searcher, field: Field, token: &str, distance: usize) -> tantivy::Result<Vec<Term>> {
+	let prefix: String = token.chars().take(FUZZY_PREFIX_LENGTH).collect();
+	let token: Vec<char> = token.chars().collect();
+	let mut expansions = Vec::new();
+	for segment_reader in searcher.segment_readers() {
+		let inverted_index = segment_reader.inverted_index(field)?;
+		let mut stream = inverted_index.terms().range().ge(prefix.as_bytes()).into_stream()?;
+		while stream.advance() {
+			let Ok(text) = std::str::from_utf8(stream.key()) else {
+				continue
+			};
+			if !text.starts_with(&prefix) {
+				break
+			}
+			let candidate: Vec<char> = text.chars().collect();
+			if candidate.len().abs_diff(token.len()) > distance {
+				continue
+			}
+			let d = get_edit_distance(&token, &candidate);
+			if d <= distance {
+				expansions.push((d, text.to_string()));
+			}
+		}
+	}
+	expansions.sort_unstable();
+	expansions.dedup();
+	Ok(expansions.into_iter()
+		.take(MAX_FUZZY_EXPANSIONS)
+		.map(|(_, text)| Term::from_field_text(field, &text))
+		.collect())
+}
+
+// Number of single character insertions, deletions, substitutions and swaps of
+// adjacent characters needed to turn one word into the other
+fn get_edit_distance(a: &[char], b: &[char]) -> usize {
+	let mut rows = vec![(0..=b.len()).collect::<Vec<usize>>()];
+	for i in 1..=a.len() {
+		let mut row = vec![i; b.len() + 1];
+		for j in 1..=b.len() {
+			let cost = usize::from(a[i - 1] != b[j - 1]);
+			row[j] = (rows[i - 1][j] + 1).min(row[j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+			if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+				row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+			}
+		}
+		rows.push(row);
+	}
+	rows[a.len()][b.len()]
+}
+
+// Prefix terms (`term*`) match any indexed term starting with the last token.
+// Earlier tokens must appear immediately before it, so that `str.start*`
+// matches `str.startswith` but not every page containing `str` and `start...`.
+fn get_prefix_queries(prefix_terms: &[String], analyzer: &mut TextAnalyzer, prefix_analyzer: &mut TextAnalyzer, fields: &[(Field, f32)], terms: &mut Vec<String>) -> Vec<Box<dyn Query>> {
+	let mut res: Vec<Box<dyn Query>> = Vec::new();
+	for text in prefix_terms.iter().take(MAX_PREFIX_TERMS) {
+		// The prefix itself must not be stemmed, but the tokens before it
+		// have to match the stemmed tokens in the index
+		let mut tokens = Vec::new();
+		let mut token_stream = prefix_analyzer.token_stream(text);
+		while let Some(token) = token_stream.next() {
+			tokens.push(token.text.clone());
+			if tokens.len() >= MAX_PHRASE_TOKENS {
+				break
+			}
+		}
+		let Some(prefix) = tokens.pop() else {
+			continue
+		};
+		if tokens.is_empty() && prefix.chars().count() < MIN_PREFIX_LENGTH {
+			continue
+		}
+		let mut stemmed_tokens = Vec::new();
+		for token in &tokens {
+			let mut token_stream = analyzer.token_stream(token);
+			while let Some(token) = token_stream.next() {
+				stemmed_tokens.push(token.text.clone());
+			}
+		}
+		terms.extend(tokens);
+		terms.push(prefix.clone());
+		for (f, b) in fields {
+			let phrase = stemmed_tokens.iter()
+				.chain(std::iter::once(&prefix))
+				.map(|t| Term::from_field_text(*f, t.as_str()))
+				.collect();
+			let mut q = PhrasePrefixQuery::new(phrase);
+			q.set_max_expansions(MAX_PREFIX_EXPANSIONS);
+			let q = Box::new(BoostQuery::new(Box::new(q), *b));
+			res.push(q);
+		}
+	}
+	res
+}
+
 // Parse the basic parts of the search query
 fn parse_query(text: &str) -> UserQuery {
 	let mut domain = None;
+	let mut version = None;
 	let mut text_terms = String::new();
+	let mut exact_terms = String::new();
 	let mut text_phrases = Vec::new();
 	let mut code_phrases = Vec::new();
+	let mut fuzzy_terms = Vec::new();
+	let mut prefix_terms = Vec::new();
 
 	let mut last_end = 0;
-	let code = r#"`([^`]+)`|"([^`"]+)"|(\s|^)site:([a-z0-9-\.]+)"#;
+	let code = r#"`([^`]+)`|"([^`"]+)"|(\s|^)site:([a-z0-9-\.]+)|([^\s`"~*]+)~([12])?|([^\s`"~*]+)\*|(\s|^)\+([^\s`"~*]+)|(\s|^)version:([^\s`"~*]+)"#;
 	let re = regex::Regex::new(code).unwrap();
 	for capture in re.captures_iter(text) {
 		if let Some(x) = capture.get(1) {
@@ -228,6 +600,16 @@ fn parse_query(text: &str) -> UserQuery {
 			text_phrases.push(x.as_str().to_string())
 		} else if let Some(x) = capture.get(4) {
 			domain = Some(x.as_str().to_string())
+		} else if let Some(x) = capture.get(5) {
+			let distance = capture.get(6).map_or(1, |d| d.as_str().parse().unwrap());
+			fuzzy_terms.push((x.as_str().to_string(), distance))
+		} else if let Some(x) = capture.get(7) {
+			prefix_terms.push(x.as_str().to_string())
+		} else if let Some(x) = capture.get(9) {
+			exact_terms.push_str(x.as_str());
+			exact_terms.push(' ');
+		} else if let Some(x) = capture.get(11) {
+			version = Some(x.as_str().to_string())
 		}
 		let mat = capture.get(0).unwrap();
 		if mat.start() > last_end {
@@ -237,5 +619,219 @@ fn parse_query(text: &str) -> UserQuery {
 		last_end = mat.end();
 	}
 	text_terms.push_str(&text[last_end..]);
-	UserQuery {domain, text_terms, text_phrases, code_phrases}
+	UserQuery {domain, version, text_terms, exact_terms, text_phrases, code_phrases, fuzzy_terms, prefix_terms}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::PathBuf;
+	use tantivy::{IndexWriter, TantivyDocument};
+	use crate::index::{self, AnalyzerConfig, IndexManifest};
+	use super::*;
+
+	const FILLER_TEXT: &str = "Filler text so that every page has a few words in common with other pages.";
+
+	// An index in a temporary directory, which is removed when it is dropped
+	struct TestIndex {
+		schema: SearchEngineSchema,
+		index: tantivy::Index,
+		dir: PathBuf,
+	}
+
+	impl TestIndex {
+		// Pages are given as their URL and text
+		fn new(name: &str, analyzer: AnalyzerConfig, pages: &[(&str, &str)]) -> TestIndex {
+			let dir = std::env::temp_dir().join(format!("mini_search_engine_search_{}_{}", name, std::process::id()));
+			let _ = std::fs::remove_dir_all(&dir);
+			let schema = index::get_schema();
+			let index = index::create_index(&schema, dir.to_str().unwrap(), &IndexManifest::new("test", analyzer)).unwrap();
+			let mut writer: IndexWriter = index.writer(15_000_000).unwrap();
+			for (url, text) in pages {
+				let text = format!("{} {}", text, FILLER_TEXT);
+				let mut document = TantivyDocument::new();
+				document.add_text(schema.domain, "fixture");
+				document.add_text(schema.url, url);
+				document.add_text(schema.title, url);
+				document.add_text(schema.text, &text);
+				document.add_u64(schema.page_rank, u64::MAX / 2);
+				document.add_u64(schema.fingerprint, fingerprint::get_fingerprint(&text));
+				writer.add_document(document).unwrap();
+			}
+			writer.commit().unwrap();
+			TestIndex {schema, index, dir}
+		}
+
+		// URLs of the results, best first
+		fn search(&self, query: &str) -> Vec<String> {
+			match search(&self.schema, &self.index, &Synonyms::default(), query) {
+				Some(SearchResults::Entries(entries)) => entries.into_iter().map(|e| e.url).collect(),
+				_ => panic!("search for {} failed", query),
+			}
+		}
+	}
+
+	impl Drop for TestIndex {
+		fn drop(&mut self) {
+			let _ = std::fs::remove_dir_all(&self.dir);
+		}
+	}
+
+	#[test]
+	fn stop_words_only_add_to_the_score() {
+		let pages = [("/foo", "foo bar baz qux"), ("/the", "the foo bar baz qux"), ("/only_the", "the bar baz qux")];
+		let index = TestIndex::new("stop_words", AnalyzerConfig::default(), &pages);
+		assert_eq!(index.search("the foo"), ["/the", "/foo"]);
+		// Unless the query has nothing else in it
+		let mut results = index.search("the");
+		results.sort_unstable();
+		assert_eq!(results, ["/only_the", "/the"]);
+	}
+
+	#[test]
+	fn forced_terms_keep_stop_words() {
+		let pages = [("/foo", "foo foo bar baz qux"), ("/the", "the foo bar baz qux")];
+		let index = TestIndex::new("forced_stop_words", AnalyzerConfig::default(), &pages);
+		assert_eq!(index.search("+the foo")[0], "/the");
+		assert_eq!(index.search("+the"), ["/the"]);
+
+		let analyzer = AnalyzerConfig {stop_words: None, ..AnalyzerConfig::default()};
+		let index = TestIndex::new("all_words", analyzer, &pages);
+		assert_eq!(index.search("the foo")[0], "/the");
+	}
+
+	#[test]
+	fn skips_versions_that_are_too_long_to_search_for() {
+		let index = TestIndex::new("long_version", AnalyzerConfig::default(), &[("/foo", "foo")]);
+		let query = format!("foo version:{}", "9.".repeat(8000));
+		assert_eq!(index.search(&query), ["/foo"]);
+	}
+
+	fn get_text_terms(query: &UserQuery) -> Vec<&str> {
+		query.text_terms.split_whitespace().collect()
+	}
+
+	#[test]
+	fn parses_fuzzy_terms() {
+		let query = parse_query("foo~ bar~1 baz~2");
+		assert_eq!(query.fuzzy_terms, [("foo".to_string(), 1), ("bar".to_string(), 1), ("baz".to_string(), 2)]);
+		assert!(get_text_terms(&query).is_empty());
+
+		// Only distances 1 and 2 are allowed, anything else is a text term
+		let query = parse_query("foo~3");
+		assert_eq!(query.fuzzy_terms, [("foo".to_string(), 1)]);
+		assert_eq!(get_text_terms(&query), ["3"]);
+	}
+
+	#[test]
+	fn parses_prefix_terms() {
+		let query = parse_query("str.start* foo");
+		assert_eq!(query.prefix_terms, ["str.start"]);
+		assert_eq!(get_text_terms(&query), ["foo"]);
+	}
+
+	#[test]
+	fn parses_combined_operators() {
+		let query = parse_query("+the foo~ bar* site:docs.python.org baz~2");
+		assert_eq!(query.exact_terms.trim(), "the");
+		assert_eq!(query.fuzzy_terms, [("foo".to_string(), 1), ("baz".to_string(), 2)]);
+		assert_eq!(query.prefix_terms, ["bar"]);
+		assert_eq!(query.domain.as_deref(), Some("docs.python.org"));
+		assert!(get_text_terms(&query).is_empty());
+
+		// A fuzzy term can't also be forced
+		let query = parse_query("+foo~");
+		assert_eq!(query.fuzzy_terms, [("+foo".to_string(), 1)]);
+		assert!(query.exact_terms.is_empty());
+	}
+
+	#[test]
+	fn ignores_operators_in_quotes() {
+		let query = parse_query(r#""foo~ bar*" `baz~2` +qux"#);
+		assert_eq!(query.text_phrases, ["foo~ bar*"]);
+		assert_eq!(query.code_phrases, ["baz~2"]);
+		assert!(query.fuzzy_terms.is_empty());
+		assert!(query.prefix_terms.is_empty());
+		assert_eq!(query.exact_terms.trim(), "qux");
+	}
+
+	#[test]
+	fn counts_swapped_characters_as_one_edit() {
+		let distance = |a: &str, b: &str| get_edit_distance(&a.chars().collect::<Vec<_>>(), &b.chars().collect::<Vec<_>>());
+		assert_eq!(distance("kotlin", "kotlin"), 0);
+		assert_eq!(distance("kotlin", "kotlni"), 1);
+		assert_eq!(distance("kotlin", "kotln"), 1);
+		assert_eq!(distance("kotlin", "kottlin"), 1);
+		assert_eq!(distance("kotlin", "cotlin"), 1);
+		assert_eq!(distance("kotlin", "koltni"), 2);
+		assert_eq!(distance("", "abc"), 3);
+	}
+
+	#[test]
+	fn fuzzy_terms_keep_their_first_character() {
+		let pages = [("/kotlin", "kotlin"), ("/cotton", "cotton")];
+		let index = TestIndex::new("fuzzy", AnalyzerConfig::default(), &pages);
+		assert_eq!(index.search("kotlni~"), ["/kotlin"]);
+		assert_eq!(index.search("kotln~"), ["/kotlin"]);
+		assert!(index.search("jotlin~").is_empty());
+		assert_eq!(index.search("kotlin~ cotten~").len(), 2);
+	}
+
+	#[test]
+	fn limits_fuzzy_expansions() {
+		// Every word with one character of "kotlin" after the first replaced
+		let mut words = vec!["kotlin".to_string()];
+		for i in 1..6 {
+			for c in 'a'..='z' {
+				let mut word: Vec<char> = "kotlin".chars().collect();
+				word[i] = c;
+				words.push(word.into_iter().collect());
+			}
+		}
+		let text = words.join(" ");
+		let index = TestIndex::new("fuzzy_expansions", AnalyzerConfig::default(), &[("/kotlin", &text)]);
+		let searcher = index.index.reader().unwrap().searcher();
+		let expansions = get_fuzzy_expansions(&searcher, index.schema.text, "kotlin", 1).unwrap();
+		assert_eq!(expansions.len(), MAX_FUZZY_EXPANSIONS);
+		assert_eq!(expansions[0].value().as_str(), Some("kotlin"));
+	}
+
+	const SECTIONS: &[&str] = &[
+		"1\tos\tos — Miscellaneous operating system interfaces",
+		"2\tfiles\tFiles and Directories",
+		"3\tlinux-extended-attributes\tLinux extended attributes",
+		"2\tprocess\tProcess Management",
+		"3\tscheduler\tInterface to the scheduler",
+		"not a section",
+	];
+
+	fn get_test_deep_link(terms: &[&str]) -> Option<DeepLink> {
+		let mut analyzer = TextAnalyzer::builder(tantivy::tokenizer::SimpleTokenizer::default())
+			.filter(tantivy::tokenizer::LowerCaser)
+			.build();
+		let terms: Vec<String> = terms.iter().map(|t| t.to_string()).collect();
+		get_deep_link("https://docs.python.org/3/library/os.html", SECTIONS, &mut analyzer, &terms)
+	}
+
+	#[test]
+	fn deep_links_to_the_best_matching_heading() {
+		let link = get_test_deep_link(&["linux", "attributes"]).unwrap();
+		assert_eq!(link.url, "https://docs.python.org/3/library/os.html#linux-extended-attributes");
+		assert_eq!(link.breadcrumb, ["os — Miscellaneous operating system interfaces", "Files and Directories", "Linux extended attributes"]);
+
+		// The heading matching the most different terms wins, and the first
+		// one on a tie
+		let link = get_test_deep_link(&["interface", "scheduler", "the"]).unwrap();
+		assert_eq!(link.url, "https://docs.python.org/3/library/os.html#scheduler");
+		assert_eq!(link.breadcrumb, ["os — Miscellaneous operating system interfaces", "Process Management", "Interface to the scheduler"]);
+		let link = get_test_deep_link(&["interfaces", "interface"]).unwrap();
+		assert_eq!(link.url, "https://docs.python.org/3/library/os.html#os");
+		assert_eq!(link.breadcrumb, ["os — Miscellaneous operating system interfaces"]);
+	}
+
+	#[test]
+	fn leaves_out_deep_links_without_matching_headings() {
+		assert!(get_test_deep_link(&["socket"]).is_none());
+		assert!(get_test_deep_link(&[]).is_none());
+		assert!(get_test_deep_link(&["section"]).is_none());
+	}
 }
@@ -0,0 +1,84 @@
+use regex::Regex;
+
+// URLs listed in a sitemap, which is either a list of pages or an index of
+// other sitemaps
+pub enum Sitemap {
+	Pages(Vec<String>),
+	Index(Vec<String>),
+}
+
+// Sitemaps are simple enough that the URLs can be found without an XML
+// parser. Only the <loc> of each entry is read.
+pub fn parse(xml: &str) -> Sitemap {
+	let re = Regex::new(r"(?s)<loc>\s*(?:<!\[CDATA\[)?(.*?)(?:\]\]>)?\s*</loc>").unwrap();
+	let urls = re.captures_iter(xml).map(|c| unescape(&c[1])).collect();
+	if xml.contains("<sitemapindex") {
+		Sitemap::Index(urls)
+	} else {
+		Sitemap::Pages(urls)
+	}
+}
+
+fn unescape(text: &str) -> String {
+	text.replace("&lt;", "<")
+		.replace("&gt;", ">")
+		.replace("&quot;", "\"")
+		.replace("&apos;", "'")
+		.replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_url_sets() {
+		let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+	<url><loc>https://example.com/</loc><lastmod>2026-10-18</lastmod></url>
+	<url>
+		<loc>
+			https://example.com/docs/
+		</loc>
+	</url>
+	<url><loc><![CDATA[https://example.com/about]]></loc></url>
+</urlset>"#;
+		let Sitemap::Pages(urls) = parse(xml) else {
+			panic!("not a list of pages")
+		};
+		assert_eq!(urls, ["https://example.com/", "https://example.com/docs/", "https://example.com/about"]);
+	}
+
+	#[test]
+	fn parses_sitemap_indexes() {
+		let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+	<sitemap><loc>https://example.com/sitemap-docs.xml</loc></sitemap>
+	<sitemap><loc>https://example.com/sitemap-blog.xml.gz</loc></sitemap>
+</sitemapindex>"#;
+		let Sitemap::Index(urls) = parse(xml) else {
+			panic!("not a sitemap index")
+		};
+		assert_eq!(urls, ["https://example.com/sitemap-docs.xml", "https://example.com/sitemap-blog.xml.gz"]);
+	}
+
+	#[test]
+	fn unescapes_urls() {
+		let xml = "<urlset><url><loc>https://example.com/search?q=a&amp;page=2&amp;sort=&lt;&gt;&quot;&apos;</loc></url></urlset>";
+		let Sitemap::Pages(urls) = parse(xml) else {
+			panic!("not a list of pages")
+		};
+		assert_eq!(urls, ["https://example.com/search?q=a&page=2&sort=<>\"'"]);
+
+		// An escaped ampersand is only unescaped once
+		assert_eq!(unescape("a&amp;lt;b"), "a&lt;b");
+	}
+
+	#[test]
+	fn parses_sitemaps_without_urls() {
+		let Sitemap::Pages(urls) = parse("<urlset></urlset>") else {
+			panic!("not a list of pages")
+		};
+		assert!(urls.is_empty());
+	}
+}
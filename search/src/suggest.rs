@@ -0,0 +1,224 @@
+use std::fs;
+use std::collections::HashMap;
+use fst::{IntoStreamer, Streamer, Map, MapBuilder};
+use fst::automaton::{Automaton, Str};
+
+// Stored in the index directory next to the tantivy files
+pub const SUGGESTIONS_FILE: &str = "suggestions.fst";
+
+// Limits on the amount of work done per request. Short prefixes can match a
+// huge number of titles, so the best titles for prefixes with more keys than
+// can be scanned are found when the index is built.
+const MAX_SUGGESTION_SCAN: usize = 4096;
+const MAX_SUGGESTIONS: usize = 16;
+const MAX_PREFIX_LENGTH: usize = 256;
+const MAX_TITLE_LENGTH: usize = 128;
+
+// Each title can also be completed starting at a later word, e.g. "hashmap"
+// completes to "std::collections::HashMap"
+const MAX_KEYS_PER_TITLE: usize = 8;
+
+// Keys in the FST are the lowercased completion text, followed by a null byte
+// and the title to show to the user. The value is the best page rank of any
+// page with that title. The best titles for a prefix with too many keys are
+// stored again with the prefix instead of the completion text, marked with a
+// leading TOP_MARKER.
+const TOP_MARKER: char = '\x01';
+
+#[derive(Default)]
+pub struct SuggestionsBuilder {
+	keys: HashMap<String, u64>,
+}
+
+pub struct Suggestions {
+	map: Map<Vec<u8>>,
+}
+
+impl SuggestionsBuilder {
+	pub fn add(&mut self, title: &str, page_rank: u64) {
+		let title = get_suggestion_title(title);
+		if title.is_empty() {
+			return
+		}
+		for start in get_word_starts(&title).take(MAX_KEYS_PER_TITLE) {
+			let key = format!("{}\0{}", title[start..].to_lowercase(), title);
+			let rank = self.keys.entry(key).or_insert(0);
+			*rank = page_rank.max(*rank);
+		}
+	}
+
+	pub fn write(self, index_dir: &str) -> fst::Result<()> {
+		fs::write(index_dir.to_string() + "/" + SUGGESTIONS_FILE, self.build()?)?;
+		Ok(())
+	}
+
+	fn build(self) -> fst::Result<Vec<u8>> {
+		let mut keys: Vec<_> = self.keys.into_iter().collect();
+		keys.sort_unstable();
+		let top_keys = get_top_keys(&keys);
+		keys.extend(top_keys);
+		keys.sort_unstable();
+		let mut builder = MapBuilder::new(Vec::new())?;
+		for (key, rank) in keys {
+			builder.insert(key, rank)?;
+		}
+		builder.into_inner()
+	}
+}
+
+// Finds the prefixes of the completion text with more keys than a request
+// scans, and returns keys for the best titles of each. Keys with the same
+// prefix are next to each other once sorted, so each prefix is a range of keys.
+fn get_top_keys(keys: &[(String, u64)]) -> Vec<(String, u64)> {
+	let completions: Vec<&str> = keys.iter().map(|(k, _)| k.split('\0').next().unwrap()).collect();
+	let mut top_keys = Vec::new();
+	// Prefixes of the current key, as their length and the first key with them
+	let mut prefixes: Vec<(usize, usize)> = Vec::new();
+	for i in 0..=keys.len() {
+		let text = completions.get(i).copied().unwrap_or("");
+		while let Some(&(length, start)) = prefixes.last() {
+			if i < keys.len() && text.as_bytes().get(..length) == Some(&completions[start].as_bytes()[..length]) {
+				break
+			}
+			prefixes.pop();
+			if i - start > MAX_SUGGESTION_SCAN {
+				let prefix = &completions[start][..length];
+				for (title, rank) in get_best_titles(keys[start..i].iter().map(|(k, r)| (k.as_str(), *r)), MAX_SUGGESTIONS) {
+					top_keys.push((format!("{}{}\0{}", TOP_MARKER, prefix, title), rank));
+				}
+			}
+		}
+		let shared = prefixes.last().map_or(0, |(length, _)| *length);
+		for (j, c) in text.char_indices().skip_while(|(j, _)| *j < shared) {
+			prefixes.push((j + c.len_utf8(), i));
+		}
+	}
+	top_keys
+}
+
+// Titles of the given keys with their best page rank, best first
+fn get_best_titles<'a>(keys: impl IntoIterator<Item = (&'a str, u64)>, limit: usize) -> Vec<(String, u64)> {
+	let mut best: HashMap<String, u64> = HashMap::new();
+	for (key, rank) in keys {
+		if let Some((_, title)) = key.split_once('\0') {
+			let best_rank = best.entry(title.to_string()).or_insert(0);
+			*best_rank = rank.max(*best_rank);
+		}
+	}
+	let mut results: Vec<_> = best.into_iter().collect();
+	results.sort_unstable_by(|(t1, r1), (t2, r2)| r2.cmp(r1).then(t1.len().cmp(&t2.len())).then(t1.cmp(t2)));
+	results.truncate(limit);
+	results
+}
+
+impl Suggestions {
+	pub fn open(index_dir: &str) -> fst::Result<Suggestions> {
+		let bytes = fs::read(index_dir.to_string() + "/" + SUGGESTIONS_FILE)?;
+		Ok(Suggestions {map: Map::new(bytes)?})
+	}
+
+	// Used when the index was built without suggestions
+	pub fn empty() -> Suggestions {
+		Suggestions {map: Map::default()}
+	}
+
+	// Returns up to `limit` titles starting with the given prefix, best page
+	// rank first, and at most MAX_SUGGESTIONS
+	pub fn suggest(&self, prefix: &str, limit: usize) -> Vec<String> {
+		let prefix = prefix.trim_start().to_lowercase().replace('\0', "");
+		if prefix.is_empty() || prefix.len() > MAX_PREFIX_LENGTH {
+			return Vec::new()
+		}
+
+		let limit = limit.min(MAX_SUGGESTIONS);
+
+		let top_prefix = format!("{}{}\0", TOP_MARKER, prefix);
+		let mut keys = self.get_keys(&top_prefix, MAX_SUGGESTIONS);
+		if keys.is_empty() {
+			// Indexes built before the best titles were stored only have the
+			// first keys scanned
+			keys = self.get_keys(&prefix, MAX_SUGGESTION_SCAN);
+		}
+		get_best_titles(keys.iter().map(|(k, r)| (k.as_str(), *r)), limit).into_iter().map(|(title, _)| title).collect()
+	}
+
+	fn get_keys(&self, prefix: &str, limit: usize) -> Vec<(String, u64)> {
+		let automaton = Str::new(prefix).starts_with();
+		let mut stream = self.map.search(automaton).into_stream();
+		let mut keys = Vec::new();
+		while let Some((key, rank)) = stream.next() {
+			keys.push((String::from_utf8_lossy(key).into_owned(), rank));
+			if keys.len() >= limit {
+				break
+			}
+		}
+		keys
+	}
+}
+
+// Page titles usually end with the name of the website, e.g.
+// "Array.prototype.flatMap() - JavaScript | MDN", which isn't useful as a
+// completion
+fn get_suggestion_title(title: &str) -> String {
+	let title = [" - ", " | ", " — ", " – "].iter()
+		.filter_map(|sep| title.find(sep))
+		.min()
+		.map_or(title, |end| &title[..end]);
+	title.chars()
+		.filter(|c| !c.is_control())
+		.take(MAX_TITLE_LENGTH)
+		.collect::<String>()
+		.trim()
+		.to_string()
+}
+
+// Byte offsets of the start of the title and of each word in it, where words
+// are separated by anything other than letters and digits
+fn get_word_starts(title: &str) -> impl Iterator<Item = usize> + '_ {
+	let mut prev_is_word = false;
+	title.char_indices().filter_map(move |(i, c)| {
+		let is_word = c.is_alphanumeric();
+		let is_start = i == 0 || (is_word && !prev_is_word);
+		prev_is_word = is_word;
+		is_start.then_some(i)
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn get_suggestions(titles: &[(String, u64)]) -> Suggestions {
+		let mut builder = SuggestionsBuilder::default();
+		for (title, rank) in titles {
+			builder.add(title, *rank);
+		}
+		Suggestions {map: Map::new(builder.build().unwrap()).unwrap()}
+	}
+
+	#[test]
+	fn best_rank_first() {
+		let suggestions = get_suggestions(&[
+			("str.split".to_string(), 1),
+			("String - Rust".to_string(), 3),
+			("std::collections::HashMap".to_string(), 2),
+		]);
+		assert_eq!(suggestions.suggest("S", 8), ["String", "std::collections::HashMap", "str.split"]);
+		assert_eq!(suggestions.suggest("hash", 8), ["std::collections::HashMap"]);
+		assert!(suggestions.suggest("x", 8).is_empty());
+	}
+
+	#[test]
+	fn best_rank_first_with_many_titles() {
+		// The best titles come last in alphabetical order, after more titles
+		// than a request scans
+		let count = 2 * MAX_SUGGESTION_SCAN as u64;
+		let titles: Vec<(String, u64)> = (0..count).map(|i| (format!("a{:05}", i), i)).collect();
+		let suggestions = get_suggestions(&titles);
+		let expected: Vec<String> = (count - 3..count).rev().map(|i| format!("a{:05}", i)).collect();
+		assert_eq!(suggestions.suggest("a", 3), expected);
+		assert_eq!(suggestions.suggest("a0", 3), expected);
+		assert_eq!(suggestions.suggest("a00", 3), ["a00999", "a00998", "a00997"]);
+		assert_eq!(suggestions.suggest("a00010", 3), ["a00010"]);
+	}
+}
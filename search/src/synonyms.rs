@@ -0,0 +1,124 @@
+use std::fs;
+use std::collections::HashMap;
+use tantivy::tokenizer::TextAnalyzer;
+
+// A file with this name in the index directory replaces the built-in list
+pub const SYNONYMS_FILE: &str = "synonyms.txt";
+const DEFAULT_SYNONYMS: &str = include_str!("../synonyms.txt");
+
+// Maps an analyzed search token to the token sequences it should also match
+#[derive(Default)]
+pub struct Synonyms {
+	map: HashMap<String, Vec<Vec<String>>>,
+}
+
+impl Synonyms {
+	pub fn load(index_dir: &str, analyzer: &mut TextAnalyzer) -> std::io::Result<Synonyms> {
+		let path = index_dir.to_string() + "/" + SYNONYMS_FILE;
+		let text = match fs::read_to_string(path) {
+			Ok(text) => text,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => DEFAULT_SYNONYMS.to_string(),
+			Err(e) => return Err(e),
+		};
+		Ok(Synonyms::parse(&text, analyzer))
+	}
+
+	// See synonyms.txt for the format
+	pub fn parse(text: &str, analyzer: &mut TextAnalyzer) -> Synonyms {
+		let mut synonyms = Synonyms::default();
+		for line in text.lines() {
+			let line = line.split('#').next().unwrap().trim();
+			let (from, to) = match line.split_once("=>") {
+				Some((from, to)) => (from, to),
+				None => (line, line),
+			};
+			let from: Vec<_> = from.split(',').map(|s| analyze(s, analyzer)).collect();
+			let to: Vec<_> = to.split(',').map(|s| analyze(s, analyzer)).collect();
+			for key in &from {
+				// Multi-word terms can only appear as expansions
+				let [key] = key.as_slice() else {
+					continue
+				};
+				let entry = synonyms.map.entry(key.clone()).or_default();
+				for tokens in &to {
+					if !tokens.is_empty() && tokens.as_slice() != std::slice::from_ref(key) && !entry.contains(tokens) {
+						entry.push(tokens.clone());
+					}
+				}
+			}
+		}
+		synonyms
+	}
+
+	pub fn get(&self, token: &str) -> &[Vec<String>] {
+		self.map.get(token).map_or(&[], |v| v.as_slice())
+	}
+}
+
+fn analyze(text: &str, analyzer: &mut TextAnalyzer) -> Vec<String> {
+	let mut tokens = Vec::new();
+	let mut token_stream = analyzer.token_stream(text);
+	while let Some(token) = token_stream.next() {
+		tokens.push(token.text.clone());
+	}
+	tokens
+}
+
+#[cfg(test)]
+mod tests {
+	use tantivy::tokenizer::{SimpleTokenizer, LowerCaser, Stemmer, Language};
+	use super::*;
+
+	fn parse(text: &str) -> Synonyms {
+		let mut analyzer = TextAnalyzer::builder(SimpleTokenizer::default())
+			.filter(LowerCaser)
+			.filter(Stemmer::new(Language::English))
+			.build();
+		Synonyms::parse(text, &mut analyzer)
+	}
+
+	fn tokens(words: &[&[&str]]) -> Vec<Vec<String>> {
+		words.iter().map(|w| w.iter().map(|t| t.to_string()).collect()).collect()
+	}
+
+	#[test]
+	fn equivalent_terms_expand_to_each_other() {
+		let synonyms = parse("js, javascript, ecmascript\n");
+		assert_eq!(synonyms.get("js"), tokens(&[&["javascript"], &["ecmascript"]]));
+		assert_eq!(synonyms.get("javascript"), tokens(&[&["js"], &["ecmascript"]]));
+		assert_eq!(synonyms.get("ecmascript"), tokens(&[&["js"], &["javascript"]]));
+	}
+
+	#[test]
+	fn one_way_terms_only_expand_forwards() {
+		let synonyms = parse("fn, func => function\n");
+		assert_eq!(synonyms.get("fn"), tokens(&[&["function"]]));
+		assert_eq!(synonyms.get("func"), tokens(&[&["function"]]));
+		assert!(synonyms.get("function").is_empty());
+	}
+
+	#[test]
+	fn multi_word_terms_are_only_expansions() {
+		let synonyms = parse("regex, regular expression\nenv => environment variable\n");
+		assert_eq!(synonyms.get("regex"), tokens(&[&["regular", "express"]]));
+		assert_eq!(synonyms.get("env"), tokens(&[&["environ", "variabl"]]));
+		assert!(synonyms.get("regular").is_empty());
+		assert!(synonyms.get("express").is_empty());
+	}
+
+	#[test]
+	fn keys_go_through_the_analyzer() {
+		let synonyms = parse("Dirs, Directories # comment\n\n# only a comment\nArgs => Arguments\n");
+		assert_eq!(synonyms.get("dir"), tokens(&[&["directori"]]));
+		assert_eq!(synonyms.get("directori"), tokens(&[&["dir"]]));
+		assert_eq!(synonyms.get("arg"), tokens(&[&["argument"]]));
+		assert!(synonyms.get("Dirs").is_empty());
+		assert!(synonyms.get("comment").is_empty());
+	}
+
+	#[test]
+	fn entries_for_the_same_key_are_merged() {
+		let synonyms = parse("fn => function\nfn, func\nfn => function\n");
+		assert_eq!(synonyms.get("fn"), tokens(&[&["function"], &["func"]]));
+	}
+}
@@ -0,0 +1,139 @@
+// Query parameters which only track where a visitor came from and never change
+// the page
+const TRACKING_PARAMETER_PREFIXES: &[&str] = &["utm_", "fbclid", "gclid"];
+
+// Pages which are served for a directory URL, e.g. docs.python.org/3/ and
+// docs.python.org/3/index.html
+const DIRECTORY_INDEX_NAMES: &[&str] = &["index.html", "index.htm"];
+
+// Returns the canonical form of a URL, so that different ways of writing the
+// URL of the same page are treated as one page. Fragments, tracking parameters,
+// directory index file names and default ports are removed, and the scheme and
+// host are lowercased. The scheme is kept, since some sites are only served
+// over http. URLs which aren't http or https are left as they are.
+pub fn canonicalize(url: &str) -> String {
+	let url = url.trim();
+	let without_fragment = url.split_once('#').map_or(url, |(u, _)| u);
+	let Some((scheme, rest)) = without_fragment.split_once("://") else {
+		return url.to_string()
+	};
+	let scheme = scheme.to_ascii_lowercase();
+	if scheme != "http" && scheme != "https" {
+		return url.to_string()
+	}
+
+	let authority_end = rest.find(['/', '?']).unwrap_or(rest.len());
+	let (authority, rest) = rest.split_at(authority_end);
+	let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+
+	format!("{}://{}{}{}", scheme, get_host(&scheme, authority), get_path(path), get_query(query))
+}
+
+// Key under which URLs are compared to find pages with the same URL. Sites
+// usually serve the same pages over http and https, so http is replaced with
+// https, but only in the key.
+pub fn get_key(url: &str) -> String {
+	let url = canonicalize(url);
+	match url.strip_prefix("http://") {
+		Some(rest) => format!("https://{}", rest),
+		None => url,
+	}
+}
+
+fn get_host(scheme: &str, authority: &str) -> String {
+	let host = authority.to_ascii_lowercase();
+	let default_port = if scheme == "http" { ":80" } else { ":443" };
+	let host = host.strip_suffix(default_port).unwrap_or(&host);
+	host.trim_end_matches('.').to_string()
+}
+
+// Resolves "." and ".." segments and removes directory index file names
+fn get_path(path: &str) -> String {
+	let parts: Vec<&str> = path.split('/').skip(1).collect();
+	let mut segments = Vec::new();
+	for (i, part) in parts.iter().enumerate() {
+		let is_last = i + 1 == parts.len();
+		match *part {
+			"." | ".." => {
+				if *part == ".." {
+					segments.pop();
+				}
+				// A trailing "." or ".." still refers to a directory
+				if is_last {
+					segments.push("");
+				}
+			},
+			p if is_last && DIRECTORY_INDEX_NAMES.contains(&p) => segments.push(""),
+			p => segments.push(p),
+		}
+	}
+	"/".to_string() + &segments.join("/")
+}
+
+// Sorts the query parameters and leaves out tracking parameters
+fn get_query(query: &str) -> String {
+	let mut parameters: Vec<&str> = query.split('&')
+		.filter(|p| !p.is_empty())
+		.filter(|p| !TRACKING_PARAMETER_PREFIXES.iter().any(|prefix| p.starts_with(prefix)))
+		.collect();
+	if parameters.is_empty() {
+		return String::new()
+	}
+	parameters.sort_unstable();
+	"?".to_string() + &parameters.join("&")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn keys_http_and_https_alike() {
+		assert_eq!(canonicalize("HTTP://Example.com/a"), "http://example.com/a");
+		assert_eq!(get_key("http://example.com/a"), "https://example.com/a");
+		assert_eq!(get_key("https://example.com/a"), "https://example.com/a");
+	}
+
+	#[test]
+	fn removes_only_default_ports() {
+		assert_eq!(canonicalize("http://example.com:80/a"), "http://example.com/a");
+		assert_eq!(canonicalize("https://example.com:443/a"), "https://example.com/a");
+		assert_eq!(canonicalize("http://example.com:443/a"), "http://example.com:443/a");
+		assert_eq!(canonicalize("https://example.com:8080/a"), "https://example.com:8080/a");
+		assert_eq!(get_key("http://example.com:80/a"), get_key("https://example.com:443/a"));
+	}
+
+	#[test]
+	fn resolves_dot_segments() {
+		assert_eq!(canonicalize("https://example.com/a/./b"), "https://example.com/a/b");
+		assert_eq!(canonicalize("https://example.com/a/../b"), "https://example.com/b");
+		assert_eq!(canonicalize("https://example.com/a/b/.."), "https://example.com/a/");
+		assert_eq!(canonicalize("https://example.com/a/."), "https://example.com/a/");
+		assert_eq!(canonicalize("https://example.com/../a"), "https://example.com/a");
+	}
+
+	#[test]
+	fn strips_directory_index_names() {
+		assert_eq!(canonicalize("https://docs.python.org/3/index.html"), "https://docs.python.org/3/");
+		assert_eq!(canonicalize("https://example.com/index.htm"), "https://example.com/");
+		assert_eq!(canonicalize("https://example.com/index.html/a"), "https://example.com/index.html/a");
+		assert_eq!(canonicalize("https://example.com/about.html#team"), "https://example.com/about.html");
+	}
+
+	#[test]
+	fn sorts_query_parameters_and_drops_tracking() {
+		assert_eq!(canonicalize("https://example.com/?b=2&a=1"), "https://example.com/?a=1&b=2");
+		assert_eq!(
+			canonicalize("https://example.com/a?utm_source=x&q=1&fbclid=y&gclid=z&utm_medium=w"),
+			"https://example.com/a?q=1",
+		);
+		assert_eq!(canonicalize("https://example.com/a?utm_source=x"), "https://example.com/a");
+		assert_eq!(canonicalize("https://example.com?b&a"), "https://example.com/?a&b");
+	}
+
+	#[test]
+	fn leaves_other_schemes_alone() {
+		assert_eq!(canonicalize("mailto:Someone@Example.com"), "mailto:Someone@Example.com");
+		assert_eq!(canonicalize("ftp://Example.com/a#b"), "ftp://Example.com/a#b");
+	}
+}
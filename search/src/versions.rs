@@ -0,0 +1,102 @@
+use std::cmp::Ordering;
+use regex::Regex;
+
+const DEFAULT_VERSION_RULES: &str = include_str!("../versions.txt");
+
+// URL patterns which extract the documentation version of a page
+pub struct VersionRules {
+	patterns: Vec<Regex>,
+}
+
+impl Default for VersionRules {
+	fn default() -> VersionRules {
+		VersionRules::parse(DEFAULT_VERSION_RULES).unwrap()
+	}
+}
+
+impl VersionRules {
+	// See versions.txt for the format
+	pub fn parse(text: &str) -> Result<VersionRules, regex::Error> {
+		let patterns = text.lines()
+			.map(|line| line.trim())
+			.filter(|line| !line.is_empty() && !line.starts_with('#'))
+			.map(Regex::new)
+			.collect::<Result<_, _>>()?;
+		Ok(VersionRules {patterns})
+	}
+
+	pub fn get_version(&self, url: &str) -> Option<String> {
+		self.patterns.iter()
+			.find_map(|p| p.captures(url))
+			.and_then(|c| c.get(1))
+			.map(|m| m.as_str().to_string())
+	}
+}
+
+// Compares versions by their numeric parts, so that "3.10" is newer than "3.9".
+// A version which is a prefix of another is newer than it: sites like
+// docs.python.org/3/ serve the latest release of a series under the series
+// number, so "3" is newer than "3.12".
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+	let parts = |v: &str| -> Vec<u64> { v.split('.').map(|p| p.parse().unwrap_or(0)).collect() };
+	let (a_parts, b_parts) = (parts(a), parts(b));
+	a_parts.iter().zip(&b_parts)
+		.map(|(x, y)| x.cmp(y))
+		.find(|o| o.is_ne())
+		.unwrap_or_else(|| b_parts.len().cmp(&a_parts.len()))
+		.then(a.cmp(b))
+}
+
+// The query version:3 matches versions 3, 3.12 etc.
+pub fn get_version_pattern(version: &str) -> String {
+	format!(r"{}(\..*)?", regex::escape(version))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn gets_versions_from_urls() {
+		let rules = VersionRules::default();
+		assert_eq!(rules.get_version("https://docs.python.org/3.12/library/os.html"), Some("3.12".to_string()));
+		assert_eq!(rules.get_version("http://docs.python.org/3/"), Some("3".to_string()));
+		assert_eq!(rules.get_version("https://ruby-doc.org/core-3.1.2/String.html"), Some("3.1.2".to_string()));
+		assert_eq!(rules.get_version("https://docs.python.org/dev/library/os.html"), None);
+		assert_eq!(rules.get_version("https://example.com/3.12/"), None);
+
+		let rules = VersionRules::parse("# comment\n\n^https://a\\.com/v([0-9]+)/\n^https://a\\.com/([0-9]+)/\n").unwrap();
+		assert_eq!(rules.get_version("https://a.com/v2/x"), Some("2".to_string()));
+		assert_eq!(rules.get_version("https://a.com/4/x"), Some("4".to_string()));
+		assert!(VersionRules::parse("^https://(").is_err());
+	}
+
+	#[test]
+	fn compares_versions_by_number() {
+		assert_eq!(compare_versions("3.10", "3.9"), Ordering::Greater);
+		assert_eq!(compare_versions("2.7", "3.1"), Ordering::Less);
+		assert_eq!(compare_versions("3.12", "3.12"), Ordering::Equal);
+		assert_eq!(compare_versions("1.10.0", "1.9.5"), Ordering::Greater);
+	}
+
+	#[test]
+	fn prefers_the_series_to_its_releases() {
+		assert_eq!(compare_versions("3", "3.12"), Ordering::Greater);
+		assert_eq!(compare_versions("3.12", "3.12.1"), Ordering::Greater);
+		assert_eq!(compare_versions("3", "4.0"), Ordering::Less);
+
+		let mut versions = vec!["3.11", "2", "3", "3.12", "2.7"];
+		versions.sort_unstable_by(|a, b| compare_versions(b, a));
+		assert_eq!(versions, ["3", "3.12", "3.11", "2", "2.7"]);
+	}
+
+	#[test]
+	fn version_patterns_match_the_series() {
+		let pattern = Regex::new(&format!("^(?:{})$", get_version_pattern("3.1"))).unwrap();
+		assert!(pattern.is_match("3.1"));
+		assert!(pattern.is_match("3.1.4"));
+		assert!(!pattern.is_match("3.12"));
+		assert!(!pattern.is_match("3"));
+		assert!(!pattern.is_match("341"));
+	}
+}
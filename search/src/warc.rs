@@ -0,0 +1,264 @@
+use std::fs::{File, OpenOptions};
+use std::hash::{BuildHasher, RandomState};
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
+use flate2::bufread::GzDecoder;
+use crate::compression::{self, Compression, Position};
+
+// A record of a WARC file (ISO 28500), such as an HTTP response
+pub struct Record {
+	pub headers: Vec<(String, String)>,
+	pub content: Vec<u8>,
+}
+
+// An HTTP response stored in a response record
+pub struct HttpResponse<'a> {
+	pub status: u16,
+	pub headers: Vec<(String, String)>,
+
+	// As it was sent, which may be chunked or compressed
+	body: &'a [u8],
+}
+
+// Appends records to a WARC file, compressing each record on its own if the
+// file name ends with .gz or .zst
+pub struct WarcWriter {
+	file: File,
+	compression: Compression,
+}
+
+// Reads every record of a file in order, without keeping them in memory
+pub fn read_records(path: &Path, mut f: impl FnMut(Position, Record) -> io::Result<()>) -> io::Result<()> {
+	compression::read_frames(path, |frame| {
+		loop {
+			let position = frame.position()?;
+			let Some(record) = read_record(frame)? else {
+				return Ok(())
+			};
+			f(position, record)?;
+		}
+	})
+}
+
+// Reads one record found by read_records
+pub fn read_record_at(path: &Path, position: Position) -> io::Result<Record> {
+	let mut input = compression::open_at(path, position)?;
+	read_record(&mut input)?.ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "missing WARC record"))
+}
+
+// Returns None at the end of the input
+fn read_record(input: &mut impl BufRead) -> io::Result<Option<Record>> {
+	// Records are followed by two blank lines
+	let mut line = Vec::new();
+	loop {
+		line.clear();
+		if input.read_until(b'\n', &mut line)? == 0 {
+			return Ok(None)
+		}
+		if !line.trim_ascii().is_empty() {
+			break
+		}
+	}
+	if !line.starts_with(b"WARC/") {
+		return Err(invalid_data("not a WARC record"))
+	}
+
+	let mut headers: Vec<(String, String)> = Vec::new();
+	loop {
+		line.clear();
+		if input.read_until(b'\n', &mut line)? == 0 {
+			return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated WARC headers"))
+		}
+		let text = String::from_utf8_lossy(&line);
+		if text.trim().is_empty() {
+			break
+		}
+		// Long values can continue on lines starting with whitespace
+		if text.starts_with([' ', '\t']) {
+			if let Some((_, value)) = headers.last_mut() {
+				value.push(' ');
+				value.push_str(text.trim());
+			}
+			continue
+		}
+		let (name, value) = text.split_once(':').ok_or_else(|| invalid_data("bad WARC header"))?;
+		headers.push((name.trim().to_string(), value.trim().to_string()));
+	}
+
+	let mut record = Record {headers, content: Vec::new()};
+	let length: usize = record.header("Content-Length").and_then(|l| l.parse().ok())
+		.ok_or_else(|| invalid_data("missing Content-Length"))?;
+	record.content = vec![0; length];
+	input.read_exact(&mut record.content)?;
+	Ok(Some(record))
+}
+
+impl Record {
+	// A record with the headers every record needs
+	pub fn new(warc_type: &str, content_type: &str, content: Vec<u8>) -> Record {
+		let date = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+		Record {
+			headers: vec![
+				("WARC-Type".to_string(), warc_type.to_string()),
+				("WARC-Record-ID".to_string(), get_record_id()),
+				("WARC-Date".to_string(), date),
+				("Content-Type".to_string(), content_type.to_string()),
+				("Content-Length".to_string(), content.len().to_string()),
+			],
+			content,
+		}
+	}
+
+	pub fn with_header(mut self, name: &str, value: &str) -> Record {
+		self.headers.push((name.to_string(), value.to_string()));
+		self
+	}
+
+	pub fn header(&self, name: &str) -> Option<&str> {
+		get_header(&self.headers, name)
+	}
+
+	fn write(&self, output: &mut impl Write) -> io::Result<()> {
+		output.write_all(b"WARC/1.1\r\n")?;
+		for (name, value) in &self.headers {
+			write!(output, "{}: {}\r\n", name, value)?;
+		}
+		output.write_all(b"\r\n")?;
+		output.write_all(&self.content)?;
+		output.write_all(b"\r\n\r\n")
+	}
+}
+
+impl<'a> HttpResponse<'a> {
+	// Parses the status line and headers of a response record, or returns
+	// None if it isn't an HTTP response
+	pub fn parse(content: &'a [u8]) -> Option<HttpResponse<'a>> {
+		let (head, body) = match content.windows(4).position(|w| w == b"\r\n\r\n") {
+			Some(i) => (&content[..i], &content[i + 4..]),
+			None => (content, &content[content.len()..]),
+		};
+		let head = String::from_utf8_lossy(head);
+		let mut lines = head.lines();
+		let status_line = lines.next()?;
+		if !status_line.starts_with("HTTP/") {
+			return None
+		}
+		let status = status_line.split(' ').nth(1)?.parse().ok()?;
+		let headers = lines.filter_map(|l| {
+			let (name, value) = l.split_once(':')?;
+			Some((name.trim().to_string(), value.trim().to_string()))
+		}).collect();
+		Some(HttpResponse {status, headers, body})
+	}
+
+	pub fn header(&self, name: &str) -> Option<&str> {
+		get_header(&self.headers, name)
+	}
+
+	pub fn is_html(&self) -> bool {
+		self.header("Content-Type").is_some_and(|t| t.trim_start().to_ascii_lowercase().starts_with("text/html"))
+	}
+
+	// Returns the body as the crawler would have read it
+	pub fn get_body(&self) -> io::Result<Vec<u8>> {
+		let chunked = self.header("Transfer-Encoding").is_some_and(|e| e.eq_ignore_ascii_case("chunked"));
+		let body = if chunked { dechunk(self.body)? } else { self.body.to_vec() };
+		match self.header("Content-Encoding").map(|e| e.to_ascii_lowercase()).as_deref() {
+			None | Some("identity") => Ok(body),
+			Some("gzip" | "x-gzip") => {
+				let mut decoded = Vec::new();
+				GzDecoder::new(body.as_slice()).read_to_end(&mut decoded)?;
+				Ok(decoded)
+			},
+			Some(encoding) => Err(invalid_data(&format!("unsupported Content-Encoding {}", encoding))),
+		}
+	}
+}
+
+impl WarcWriter {
+	pub fn append(path: &Path) -> io::Result<WarcWriter> {
+		let file = OpenOptions::new().create(true).append(true).open(path)?;
+		Ok(WarcWriter {file, compression: Compression::from_path(path)})
+	}
+
+	pub fn write(&mut self, record: &Record) -> io::Result<()> {
+		// Records are written in one go, so that they can be read back from
+		// a file that is still being written
+		let mut bytes = Vec::new();
+		record.write(&mut bytes)?;
+		self.file.write_all(&self.compression.compress(&bytes)?)?;
+		self.file.flush()
+	}
+}
+
+fn get_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+	headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+}
+
+fn dechunk(mut body: &[u8]) -> io::Result<Vec<u8>> {
+	let mut output = Vec::new();
+	loop {
+		let end = body.windows(2).position(|w| w == b"\r\n").ok_or_else(|| invalid_data("bad chunk"))?;
+		let size = String::from_utf8_lossy(&body[..end]);
+		// Chunk extensions follow a semicolon
+		let size = usize::from_str_radix(size.split(';').next().unwrap().trim(), 16)
+			.map_err(|_| invalid_data("bad chunk size"))?;
+		body = &body[end + 2..];
+		if size == 0 {
+			return Ok(output)
+		}
+		let chunk = body.get(..size).ok_or_else(|| invalid_data("truncated chunk"))?;
+		output.extend_from_slice(chunk);
+		body = body.get(size + 2..).unwrap_or(&[]);
+	}
+}
+
+// Random version 4 UUID, without pulling in a crate for it
+fn get_record_id() -> String {
+	let high = RandomState::new().hash_one(0);
+	let low = RandomState::new().hash_one(1);
+	format!("<urn:uuid:{:08x}-{:04x}-4{:03x}-{:04x}-{:012x}>",
+		high >> 32, (high >> 16) & 0xffff, high & 0xfff,
+		(low >> 48) & 0x3fff | 0x8000, low & 0xffff_ffff_ffff)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn dechunks_bodies() {
+		assert_eq!(dechunk(b"5\r\nhello\r\n7\r\n, world\r\n0\r\n\r\n").unwrap(), b"hello, world");
+		assert_eq!(dechunk(b"A\r\n0123456789\r\n0\r\n\r\n").unwrap(), b"0123456789");
+		assert_eq!(dechunk(b"0\r\n\r\n").unwrap(), b"");
+
+		// Trailers after the last chunk are ignored
+		assert_eq!(dechunk(b"2\r\nok\r\n0\r\nExpires: never\r\n\r\n").unwrap(), b"ok");
+	}
+
+	#[test]
+	fn ignores_chunk_extensions() {
+		assert_eq!(dechunk(b"5;name=value\r\nhello\r\n0 ; last\r\n\r\n").unwrap(), b"hello");
+	}
+
+	#[test]
+	fn rejects_truncated_chunks() {
+		for body in [&b"5\r\nhel"[..], b"5\r\nhello\r\n", b"5\r\nhello", b"5"] {
+			let error = dechunk(body).unwrap_err();
+			assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{:?}", body);
+		}
+		assert_eq!(dechunk(b"5\r\nhel").unwrap_err().to_string(), "truncated chunk");
+		assert_eq!(dechunk(b"5\r\nhello\r\n").unwrap_err().to_string(), "bad chunk");
+	}
+
+	#[test]
+	fn rejects_bad_chunk_sizes() {
+		for body in [&b"x\r\nhello\r\n0\r\n\r\n"[..], b"\r\nhello\r\n", b"-5\r\nhello\r\n", b"fffffffffffffffff\r\n"] {
+			assert_eq!(dechunk(body).unwrap_err().to_string(), "bad chunk size", "{:?}", body);
+		}
+	}
+}
@@ -0,0 +1,68 @@
+// Format of the crawled pages read by the indexer, either one JSON file per
+// page or one line per page of a JSON Lines file
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Webpage {
+	#[serde(default)]
+	pub url: String,
+
+	#[serde(default)]
+	pub domain: String,
+
+	pub links: Vec<Link>,
+	pub title: String,
+	pub text: String,
+	pub headings: String,
+	pub code: String,
+
+	// Parts of the page starting at headings with an anchor, which only newer
+	// crawls have
+	#[serde(default)]
+	pub sections: Vec<Section>,
+}
+
+// Older crawls only have the URL of each link
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum Link {
+	Url(String),
+	Anchor {
+		url: String,
+		#[serde(default)]
+		text: String,
+	},
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Hash)]
+pub struct Section {
+	// Anchor of the heading, empty for the text before the first heading
+	#[serde(default)]
+	pub id: String,
+
+	#[serde(default)]
+	pub heading: String,
+
+	// 1 to 6 for h1 to h6
+	#[serde(default)]
+	pub level: u8,
+
+	pub text: String,
+
+	#[serde(default)]
+	pub code: String,
+}
+
+impl Link {
+	pub fn url(&self) -> &str {
+		match self {
+			Link::Url(url) => url,
+			Link::Anchor {url, ..} => url,
+		}
+	}
+
+	pub fn text(&self) -> &str {
+		match self {
+			Link::Url(_) => "",
+			Link::Anchor {text, ..} => text,
+		}
+	}
+}
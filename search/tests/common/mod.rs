@@ -0,0 +1,56 @@
+// Helpers shared by the integration tests. Each test file only uses some of
+// them.
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use mini_search_engine::index;
+
+pub const LONG_TEXT: &str = "This paragraph is long enough for the crawler to consider the page worth \
+	indexing, since pages with less than two hundred characters of text are usually just lists of \
+	links or error pages that would only add noise to the search results.";
+
+pub fn get_temp_dir(name: &str) -> PathBuf {
+	let dir = std::env::temp_dir().join(format!("mini_search_engine_{}_{}", name, std::process::id()));
+	let _ = std::fs::remove_dir_all(&dir);
+	std::fs::create_dir_all(&dir).unwrap();
+	dir
+}
+
+// A page as the crawler saves it, which tests can add more fields to
+pub fn get_page(url: &str, title: &str, text: &str) -> serde_json::Value {
+	serde_json::json!({"url": url, "links": [], "title": title, "headings": "", "code": "", "text": text})
+}
+
+// Writes pages to a JSON Lines file, as the input of the indexer
+pub fn write_jsonl(path: &Path, pages: &[serde_json::Value]) -> Vec<u8> {
+	let mut lines = Vec::new();
+	for page in pages {
+		serde_json::to_writer(&mut lines, page).unwrap();
+		lines.push(b'\n');
+	}
+	std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+	std::fs::write(path, &lines).unwrap();
+	lines
+}
+
+pub fn run_indexer(input: &Path, index_dir: &Path, args: &[&str]) {
+	let status = Command::new(env!("CARGO_BIN_EXE_indexer"))
+		.args(args)
+		.arg(input).arg(index_dir)
+		.status().unwrap();
+	assert!(status.success());
+}
+
+// Stored fields of every document in an index, in a stable order
+pub fn read_documents(index_dir: &Path) -> Vec<String> {
+	use tantivy::Document;
+	let index = index::open_index(index_dir.to_str().unwrap()).unwrap();
+	let searcher = index.reader().unwrap().searcher();
+	let addresses = searcher.search(&tantivy::query::AllQuery, &tantivy::collector::DocSetCollector).unwrap();
+	let mut documents: Vec<String> = addresses.into_iter()
+		.map(|a| searcher.doc::<tantivy::TantivyDocument>(a).unwrap().to_json(&index.schema()))
+		.collect();
+	documents.sort_unstable();
+	documents
+}
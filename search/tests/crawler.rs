@@ -0,0 +1,474 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use mini_search_engine::crawl_state::{CrawlState, CRAWL_STATE_FILE};
+use mini_search_engine::warc;
+use mini_search_engine::webpage::Webpage;
+
+mod common;
+use common::{LONG_TEXT, get_temp_dir, read_documents, run_indexer};
+
+// A request seen by the fixture server
+struct Request {
+	path: String,
+	user_agent: String,
+	if_none_match: Option<String>,
+	time: Instant,
+}
+
+// Serves responses from a handler on a local port, and returns the base URL
+// and the requests seen so far
+fn start_server(handler: impl Fn(&Request) -> String + Send + 'static) -> (String, Arc<Mutex<Vec<Request>>>) {
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	let address = listener.local_addr().unwrap();
+	let requests = Arc::new(Mutex::new(Vec::new()));
+	let log = requests.clone();
+	std::thread::spawn(move || {
+		for stream in listener.incoming() {
+			let mut stream = stream.unwrap();
+			let mut request_line = String::new();
+			let mut reader = BufReader::new(stream.try_clone().unwrap());
+			reader.read_line(&mut request_line).unwrap();
+			let mut user_agent = String::new();
+			let mut if_none_match = None;
+			loop {
+				let mut line = String::new();
+				if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+					break
+				}
+				if let Some((name, value)) = line.split_once(':') {
+					if name.eq_ignore_ascii_case("user-agent") {
+						user_agent = value.trim().to_string();
+					} else if name.eq_ignore_ascii_case("if-none-match") {
+						if_none_match = Some(value.trim().to_string());
+					}
+				}
+			}
+			let path = request_line.split(' ').nth(1).unwrap_or("/").to_string();
+			let request = Request {path, user_agent, if_none_match, time: Instant::now()};
+			let response = handler(&request);
+			log.lock().unwrap().push(request);
+			stream.write_all(response.as_bytes()).unwrap();
+		}
+	});
+	(format!("http://{}", address), requests)
+}
+
+// Serves fixed pages
+fn serve_pages(pages: HashMap<&'static str, (&'static str, String)>) -> impl Fn(&Request) -> String {
+	move |request| match pages.get(request.path.as_str()) {
+		Some((content_type, body)) => format!(
+			"HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+			content_type, body.len(), body),
+		None => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+	}
+}
+
+fn run_crawler(dir: &Path, config: serde_json::Value) -> HashMap<String, Webpage> {
+	run_crawler_with_args(dir, config, &[])
+}
+
+fn run_crawler_with_args(dir: &Path, config: serde_json::Value, args: &[&str]) -> HashMap<String, Webpage> {
+	let config_path = dir.join("domains.json");
+	std::fs::write(&config_path, config.to_string()).unwrap();
+	let output_dir = dir.join("output");
+
+	let status = Command::new(env!("CARGO_BIN_EXE_crawler"))
+		.arg("--config").arg(&config_path)
+		.arg("--threads").arg("2")
+		.args(args)
+		.arg("fixture").arg(&output_dir)
+		.status().unwrap();
+	assert!(status.success());
+	read_output(&output_dir.join("fixture"))
+}
+
+fn read_output(dir: &Path) -> HashMap<String, Webpage> {
+	let mut pages = HashMap::new();
+	for entry in std::fs::read_dir(dir).unwrap() {
+		let entry = entry.unwrap();
+		let name = entry.file_name().into_string().unwrap();
+		if name == CRAWL_STATE_FILE || !name.ends_with(".json") {
+			continue
+		}
+		let webpage: Webpage = serde_json::from_str(&std::fs::read_to_string(entry.path()).unwrap()).unwrap();
+		pages.insert(webpage.url.clone(), webpage);
+	}
+	pages
+}
+
+#[test]
+fn crawls_whitelisted_pages() {
+	let index = format!("<html><head><title>Fixture docs</title>\
+		<meta name=\"description\" content=\"Docs for testing\"></head><body>\
+		<h1>Fixture docs</h1><p>{}</p>\
+		<a href=\"guide.html\">The <b>guide</b></a> <a href=\"/docs/short.html\">Short</a>\
+		<a href=\"/docs/private/secret.html\">Secret</a> <a href=\"/elsewhere.html\">Elsewhere</a>\
+		<a href=\"mailto:someone@example.com\">Mail</a> <a href=\"#top\">Top</a>\
+		</body></html>", LONG_TEXT);
+	let guide = format!("<html><head><title>Guide</title></head><body>\
+		<p>{}</p><h2 id=\"install\">Installing</h2><p>Run <code>make install</code></p>\
+		<a href=\"index.html\">Back to the docs</a><a href=\"data.json\">Data</a>\
+		</body></html>", LONG_TEXT);
+	let pages = HashMap::from([
+		("/docs/index.html", ("text/html", index)),
+		("/docs/guide.html", ("text/html; charset=utf-8", guide)),
+		("/docs/short.html", ("text/html", "<html><body><p>Too short</p></body></html>".to_string())),
+		("/docs/data.json", ("application/json", "{}".to_string())),
+		("/docs/private/secret.html", ("text/html", format!("<p>{}</p>", LONG_TEXT))),
+		("/elsewhere.html", ("text/html", format!("<p>{}</p>", LONG_TEXT))),
+	]);
+	let (base, _) = start_server(serve_pages(pages));
+
+	let dir = get_temp_dir("crawler");
+	let config = serde_json::json!({
+		"whitelists": {"fixture": [format!("{}/docs/index.html", base), format!("{}/docs/", base)]},
+		"blacklists": {"fixture": [format!("{}/docs/private/", base)]},
+		"limits": {"fixture": {"delay": 0}},
+	});
+	let pages = run_crawler(&dir, config);
+	let mut urls: Vec<_> = pages.keys().cloned().collect();
+	urls.sort();
+	assert_eq!(urls, [format!("{}/docs/guide.html", base), format!("{}/docs/index.html", base)]);
+
+	let index = &pages[&format!("{}/docs/index.html", base)];
+	assert_eq!(index.title, "Fixture docs");
+	assert_eq!(index.headings, "Fixture docs\nDocs for testing\nFixture docs");
+	assert!(index.domain.starts_with("127.0.0.1:"));
+	let links: Vec<_> = index.links.iter().map(|l| (l.url().to_string(), l.text().to_string())).collect();
+	assert_eq!(links, [
+		(format!("{}/docs/guide.html", base), "The guide".to_string()),
+		(format!("{}/docs/short.html", base), "Short".to_string()),
+	]);
+
+	let guide = &pages[&format!("{}/docs/guide.html", base)];
+	assert_eq!(guide.code, "make install");
+	assert!(guide.text.starts_with("This paragraph"));
+	assert!(guide.text.contains("Installing\nRun\nmake install\nBack to the docs"));
+	let sections: Vec<_> = guide.sections.iter().map(|s| (s.id.as_str(), s.heading.as_str(), s.level)).collect();
+	assert_eq!(sections, [("", "", 0), ("install", "Installing", 2)]);
+	assert_eq!(guide.sections[1].text, "Run\nmake install\nBack to the docs\nData");
+
+	std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn honors_robots_txt_and_rate_limits() {
+	let robots = "User-agent: *\nDisallow: /\n\n\
+		User-agent: fixturebot\nDisallow: /docs/private\nAllow: /docs/private/public.html\nCrawl-delay: 0.2\n";
+	let index = format!("<html><head><title>Fixture docs</title></head><body><p>{}</p>\
+		<a href=\"private/secret.html\">Secret</a> <a href=\"private/public.html\">Public</a>\
+		<a href=\"busy.html\">Busy</a></body></html>", LONG_TEXT);
+	let pages = HashMap::from([
+		("/robots.txt", ("text/plain", robots.to_string())),
+		("/docs/index.html", ("text/html", index)),
+		("/docs/busy.html", ("text/html", format!("<p>{}</p>", LONG_TEXT))),
+		("/docs/private/secret.html", ("text/html", format!("<p>{}</p>", LONG_TEXT))),
+		("/docs/private/public.html", ("text/html", format!("<p>{}</p>", LONG_TEXT))),
+	]);
+	let serve = serve_pages(pages);
+	// The first request for busy.html is rate limited
+	let throttled = Mutex::new(false);
+	let (base, requests) = start_server(move |request| {
+		if request.path == "/docs/busy.html" && !std::mem::replace(&mut *throttled.lock().unwrap(), true) {
+			return "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 1\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+		}
+		serve(request)
+	});
+
+	let dir = get_temp_dir("crawler_robots");
+	let config = serde_json::json!({
+		"whitelists": {"fixture": [format!("{}/docs/index.html", base), format!("{}/docs/", base)]},
+		"limits": {"fixture": {"user_agent": "FixtureBot/1.0", "delay": 0, "concurrency": 2}},
+	});
+	let pages = run_crawler(&dir, config);
+	let mut urls: Vec<_> = pages.keys().cloned().collect();
+	urls.sort();
+	assert_eq!(urls, [
+		format!("{}/docs/busy.html", base),
+		format!("{}/docs/index.html", base),
+		format!("{}/docs/private/public.html", base),
+	]);
+
+	let requests = requests.lock().unwrap();
+	let paths: Vec<_> = requests.iter().map(|r| r.path.as_str()).collect();
+	assert_eq!(paths[..2], ["/robots.txt", "/docs/index.html"]);
+	assert_eq!(paths.iter().filter(|p| **p == "/docs/busy.html").count(), 2);
+	assert!(!paths.contains(&"/docs/private/secret.html"));
+	assert!(requests.iter().all(|r| r.user_agent == "FixtureBot/1.0"));
+
+	// Crawl-delay applies even though two pages could be fetched at once
+	for pair in requests.windows(2) {
+		assert!(pair[1].time - pair[0].time >= Duration::from_millis(150));
+	}
+	let retried: Vec<_> = requests.iter().filter(|r| r.path == "/docs/busy.html").collect();
+	assert!(retried[1].time - retried[0].time >= Duration::from_millis(900));
+
+	std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn resumes_interrupted_crawl() {
+	let page = |links: &str| format!("<html><body><p>{}</p>{}</body></html>", LONG_TEXT, links);
+	let pages = HashMap::from([
+		("/docs/index.html", ("text/html", page("<a href=\"a.html\">A</a><a href=\"b.html\">B</a>"))),
+		("/docs/a.html", ("text/html", page("<a href=\"c.html\">C</a>"))),
+		("/docs/b.html", ("text/html", page(""))),
+		("/docs/c.html", ("text/html", page("<a href=\"index.html\">Index</a>"))),
+	]);
+	let (base, requests) = start_server(serve_pages(pages));
+
+	// The crawl stopped after the index and a.html were fetched
+	let dir = get_temp_dir("crawler_resume");
+	let state = CrawlState {
+		start: Some("2026-10-01T00:00:00+00:00".to_string()),
+		end: None,
+		complete: false,
+		frontier: vec![format!("{}/docs/b.html", base), format!("{}/docs/c.html", base)],
+		visited: vec![format!("{}/docs/index.html", base), format!("{}/docs/a.html", base)],
+		pages: HashMap::new(),
+		sitemap_urls: Vec::new(),
+	};
+	let domain_dir = dir.join("output").join("fixture");
+	state.write(&domain_dir).unwrap();
+	let config = serde_json::json!({
+		"whitelists": {"fixture": [format!("{}/docs/index.html", base), format!("{}/docs/", base)]},
+		"limits": {"fixture": {"delay": 0}},
+	});
+	let pages = run_crawler(&dir, config.clone());
+	let mut urls: Vec<_> = pages.keys().cloned().collect();
+	urls.sort();
+	assert_eq!(urls, [format!("{}/docs/b.html", base), format!("{}/docs/c.html", base)]);
+
+	let state = CrawlState::read(&domain_dir).unwrap().unwrap();
+	assert!(state.complete);
+	assert!(state.frontier.is_empty());
+	assert_eq!(state.visited.len(), 4);
+	assert_eq!(state.start.as_deref(), Some("2026-10-01T00:00:00+00:00"));
+	assert!(state.end.is_some());
+
+	// A complete crawl isn't started again
+	let request_count = requests.lock().unwrap().len();
+	run_crawler(&dir, config);
+	assert_eq!(requests.lock().unwrap().len(), request_count);
+
+	std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn recrawl_keeps_pages_that_cannot_be_fetched() {
+	let page = |links: &str| format!("<html><body><p>{}</p>{}</body></html>", LONG_TEXT, links);
+	let index = page("<a href=\"a.html\">A</a><a href=\"b.html\">B</a>");
+	let a = page("<a href=\"c.html\">C</a>");
+	let serve = serve_pages(HashMap::from([
+		("/docs/b.html", ("text/html", page(""))),
+		("/docs/c.html", ("text/html", page(""))),
+	]));
+	// After the first crawl, the index is unchanged, a.html is down and
+	// b.html was removed
+	let recrawling = Arc::new(AtomicBool::new(false));
+	let recrawling_server = recrawling.clone();
+	let (base, requests) = start_server(move |request| {
+		let recrawling = recrawling_server.load(Ordering::SeqCst);
+		let ok = |body: &str, etag: &str| format!(
+			"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nETag: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+			etag, body.len(), body);
+		match request.path.as_str() {
+			"/docs/index.html" if request.if_none_match.as_deref() == Some("\"v1\"") => {
+				"HTTP/1.1 304 Not Modified\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+			},
+			"/docs/index.html" => ok(&index, "\"v1\""),
+			"/docs/a.html" if recrawling => "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+			"/docs/a.html" => ok(&a, "\"a1\""),
+			"/docs/b.html" if recrawling => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+			_ => serve(request),
+		}
+	});
+
+	let dir = get_temp_dir("crawler_recrawl");
+	let config = serde_json::json!({
+		"whitelists": {"fixture": [format!("{}/docs/index.html", base), format!("{}/docs/", base)]},
+		"limits": {"fixture": {"delay": 0}},
+	});
+	let pages = run_crawler(&dir, config.clone());
+	assert_eq!(pages.len(), 4);
+
+	recrawling.store(true, Ordering::SeqCst);
+	let pages = run_crawler_with_args(&dir, config.clone(), &["--restart"]);
+	assert_eq!(pages.len(), 4);
+	let index_requests: Vec<_> = requests.lock().unwrap().iter()
+		.filter(|r| r.path == "/docs/index.html")
+		.map(|r| r.if_none_match.clone())
+		.collect();
+	assert_eq!(index_requests, [None, Some("\"v1\"".to_string())]);
+	// c.html is only linked from a.html, which was kept
+	assert_eq!(requests.lock().unwrap().iter().filter(|r| r.path == "/docs/c.html").count(), 2);
+
+	let domain_dir = dir.join("output").join("fixture");
+	let state = CrawlState::read(&domain_dir).unwrap().unwrap();
+	let b = &state.pages[&format!("{}/docs/b.html", base)];
+	assert_eq!((b.missing, b.gone), (1, false));
+	assert_eq!(state.pages[&format!("{}/docs/a.html", base)].etag.as_deref(), Some("\"a1\""));
+
+	// b.html is deleted once it has been missing for three crawls
+	run_crawler_with_args(&dir, config.clone(), &["--restart"]);
+	let pages = run_crawler_with_args(&dir, config, &["--restart"]);
+	assert!(!pages.contains_key(&format!("{}/docs/b.html", base)));
+	assert_eq!(pages.len(), 3);
+	assert!(CrawlState::read(&domain_dir).unwrap().unwrap().pages[&format!("{}/docs/b.html", base)].gone);
+
+	std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn seeds_frontier_from_sitemaps() {
+	let page = format!("<html><body><p>{}</p></body></html>", LONG_TEXT);
+	let serve = serve_pages(HashMap::from([
+		("/docs/index.html", ("text/html", page.clone())),
+		("/docs/orphan.html", ("text/html", page.clone())),
+		("/docs/other.html", ("text/html", page.clone())),
+		("/elsewhere.html", ("text/html", page)),
+	]));
+	// Sitemaps have absolute URLs, so they are made once the port is known
+	let base_slot = Arc::new(Mutex::new(String::new()));
+	let server_base = base_slot.clone();
+	let (base, requests) = start_server(move |request| {
+		let base = server_base.lock().unwrap().clone();
+		let xml = match request.path.as_str() {
+			"/robots.txt" => format!("User-agent: *\nDisallow: /docs/private/\n\nSitemap: {}/sitemap_index.xml\n", base),
+			"/sitemap_index.xml" => format!("<?xml version=\"1.0\"?><sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\
+				<sitemap><loc>{}/sitemap_docs.xml</loc></sitemap></sitemapindex>", base),
+			"/sitemap_docs.xml" => format!("<?xml version=\"1.0\"?><urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\
+				<url><loc>{0}/docs/orphan.html</loc></url>\
+				<url><loc>\n  {0}/docs/missing.html?a=1&amp;b=2\n</loc><lastmod>2026-10-01</lastmod></url>\
+				<url><loc>{0}/elsewhere.html</loc></url></urlset>", base),
+			"/other_sitemap.xml" => format!("<urlset><url><loc>{}/docs/other.html</loc></url></urlset>", base),
+			_ => return serve(request),
+		};
+		format!("HTTP/1.1 200 OK\r\nContent-Type: application/xml\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", xml.len(), xml)
+	});
+	*base_slot.lock().unwrap() = base.clone();
+
+	let dir = get_temp_dir("crawler_sitemaps");
+	let config = serde_json::json!({
+		"whitelists": {"fixture": [format!("{}/docs/index.html", base), format!("{}/docs/", base)]},
+		"limits": {"fixture": {"delay": 0}},
+		"sitemaps": {"fixture": [format!("{}/other_sitemap.xml", base)]},
+	});
+	let pages = run_crawler(&dir, config);
+	let mut urls: Vec<_> = pages.keys().cloned().collect();
+	urls.sort();
+	assert_eq!(urls, [
+		format!("{}/docs/index.html", base),
+		format!("{}/docs/orphan.html", base),
+		format!("{}/docs/other.html", base),
+	]);
+	assert!(!requests.lock().unwrap().iter().any(|r| r.path == "/elsewhere.html"));
+	assert!(requests.lock().unwrap().iter().any(|r| r.path == "/docs/missing.html?a=1&b=2"));
+
+	let state = CrawlState::read(&dir.join("output").join("fixture")).unwrap().unwrap();
+	let mut sitemap_urls = state.sitemap_urls.clone();
+	sitemap_urls.sort();
+	assert_eq!(sitemap_urls, [
+		format!("{}/docs/missing.html?a=1&b=2", base),
+		format!("{}/docs/orphan.html", base),
+		format!("{}/docs/other.html", base),
+	]);
+
+	std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn extracts_main_content() {
+	let index = format!("<html><head><title>Fixture docs</title></head><body>\
+		<div id=\"cookie-banner\">We use cookies</div>\
+		<nav><h3>Menu</h3><a href=\"guide.html\">Guide</a></nav>\
+		<div class=\"sidebar\"><h2 id=\"related\">Related pages</h2></div>\
+		<div class=\"content\"><h1 id=\"intro\">Introduction</h1><p>{}</p><pre>make all</pre></div>\
+		<footer>Copyright</footer></body></html>", LONG_TEXT);
+	let pages = HashMap::from([
+		("/docs/index.html", ("text/html", index.clone())),
+		("/docs/guide.html", ("text/html", format!("<main><p>{}</p></main><p>Outside main</p>", LONG_TEXT))),
+	]);
+	let (base, _) = start_server(serve_pages(pages));
+
+	let dir = get_temp_dir("crawler_extract");
+	let rules_path = dir.join("extract_rules.txt");
+	std::fs::write(&rules_path, "* remove nav\n* remove footer\n* remove #cookie-banner\n* main main\nfixture main div.content\n").unwrap();
+	let config = serde_json::json!({
+		"whitelists": {"fixture": [format!("{}/docs/index.html", base), format!("{}/docs/", base)]},
+		"limits": {"fixture": {"delay": 0}},
+	});
+	let pages = run_crawler_with_args(&dir, config, &["--extract-rules", rules_path.to_str().unwrap()]);
+
+	let page = &pages[&format!("{}/docs/index.html", base)];
+	assert_eq!(page.title, "Fixture docs");
+	assert_eq!(page.headings, "Fixture docs\nIntroduction");
+	assert_eq!(page.text, format!("Introduction\n{}\nmake all", LONG_TEXT));
+	assert_eq!(page.code, "make all");
+	let sections: Vec<_> = page.sections.iter().map(|s| s.id.as_str()).collect();
+	assert_eq!(sections, ["intro"]);
+	// Links in removed elements are still followed
+	assert_eq!(page.links.iter().map(|l| l.url()).collect::<Vec<_>>(), [format!("{}/docs/guide.html", base)]);
+	assert_eq!(pages[&format!("{}/docs/guide.html", base)].text, LONG_TEXT);
+
+	// The HTML is saved as it was received
+	let html_path = dir.join("output").join("fixture").join(format!("{}/docs/index.html.html", base).replace('/', "%2F"));
+	assert_eq!(std::fs::read_to_string(html_path).unwrap(), index);
+
+	std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn archives_crawl_as_warc() {
+	let index = format!("<html><head><title>Fixture docs</title></head><body>\
+		<nav><a href=\"guide.html\">Guide</a></nav><p>{}</p></body></html>", LONG_TEXT);
+	let pages = HashMap::from([
+		("/docs/index.html", ("text/html", index.clone())),
+		("/docs/guide.html", ("text/html", format!("<p>{}</p><a href=\"index.html\">Home</a>", LONG_TEXT))),
+		("/docs/short.html", ("text/html", "<p>Too short</p>".to_string())),
+	]);
+	let (base, _) = start_server(serve_pages(pages));
+
+	let dir = get_temp_dir("crawler_warc");
+	let archive_dir = dir.join("archive");
+	std::fs::create_dir_all(&archive_dir).unwrap();
+	let warc_path = archive_dir.join("fixture.warc.gz");
+	let config = serde_json::json!({
+		"whitelists": {"fixture": [format!("{}/docs/index.html", base), format!("{}/docs/", base)]},
+		"limits": {"fixture": {"delay": 0}},
+	});
+	run_crawler_with_args(&dir, config, &["--warc", warc_path.to_str().unwrap()]);
+
+	// Only saved pages are archived, after a warcinfo record
+	let mut records = Vec::new();
+	warc::read_records(&warc_path, |_, record| {
+		records.push(record);
+		Ok(())
+	}).unwrap();
+	assert_eq!(records[0].header("WARC-Type"), Some("warcinfo"));
+	let responses: HashMap<&str, &warc::Record> = records[1..].iter()
+		.map(|r| (r.header("WARC-Target-URI").unwrap(), r))
+		.collect();
+	assert_eq!(responses.len(), 2);
+	let response = warc::HttpResponse::parse(&responses[format!("{}/docs/index.html", base).as_str()].content).unwrap();
+	assert_eq!(response.status, 200);
+	assert_eq!(response.get_body().unwrap(), index.as_bytes());
+
+	// Indexing the archive gives the same index as extracting the saved HTML
+	// again
+	run_indexer(&archive_dir, &dir.join("warc_index"), &[]);
+	run_indexer(&dir.join("output"), &dir.join("index"), &["--reextract"]);
+	let documents = read_documents(&dir.join("warc_index"));
+	assert_eq!(documents.len(), 2);
+	// The fixture is only served over http, which the document URL keeps
+	assert!(documents.iter().any(|d| d.contains(&format!("\"url\":[\"{}/docs/\"]", base))));
+	assert_eq!(documents, read_documents(&dir.join("index")));
+
+	std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,130 @@
+use std::io::Write;
+use std::process::Command;
+use mini_search_engine::crawl_state::{self, CrawlState};
+use mini_search_engine::jsonl;
+
+mod common;
+use common::{LONG_TEXT, get_page, get_temp_dir, read_documents, run_indexer, write_jsonl};
+
+#[test]
+fn converts_output_to_jsonl() {
+	// Crawler output with a page whose URL has "%2F" in it, which the file
+	// name can't tell apart from "/"
+	let dir = get_temp_dir("indexer_jsonl");
+	let output_dir = dir.join("output").join("fixture");
+	std::fs::create_dir_all(&output_dir).unwrap();
+	let mut index_page = get_page("https://fixture.example/docs/index.html", "Docs", LONG_TEXT);
+	index_page["links"] = serde_json::json!([{"url": "https://fixture.example/docs/a%2Fb.html", "text": "Slash"}]);
+	let escaped_page = get_page("https://fixture.example/docs/a%2Fb.html", "Escaped", LONG_TEXT);
+	for page in [index_page, escaped_page] {
+		let url = page["url"].as_str().unwrap();
+		let name = url.replace('/', "%2F");
+		std::fs::write(output_dir.join(format!("{}.json", name)), page.to_string()).unwrap();
+		let html = format!("<title>{}</title><p>{}</p>", page["title"].as_str().unwrap(), LONG_TEXT);
+		std::fs::write(output_dir.join(format!("{}.html", name)), html).unwrap();
+	}
+	CrawlState::default().write(&output_dir).unwrap();
+
+	let mut indexes = Vec::new();
+	for format in ["jsonl", "jsonl.gz", "jsonl.zst"] {
+		let jsonl_dir = dir.join(format);
+		let status = Command::new(env!("CARGO_BIN_EXE_convert"))
+			.arg("--format").arg(format)
+			.arg(dir.join("output")).arg(&jsonl_dir)
+			.status().unwrap();
+		assert!(status.success());
+		assert!(crawl_state::get_domain_state_path(&jsonl_dir, "fixture").is_file());
+
+		// The URL is kept as it was crawled
+		let mut urls = Vec::new();
+		jsonl::read_urls(&jsonl_dir.join(format!("fixture.{}", format)), |_, _, url| {
+			urls.push(url.unwrap());
+			Ok(())
+		}).unwrap();
+		urls.sort_unstable();
+		assert_eq!(urls, ["https://fixture.example/docs/a%2Fb.html", "https://fixture.example/docs/index.html"]);
+
+		run_indexer(&jsonl_dir, &dir.join(format!("{}_index", format)), &[]);
+		run_indexer(&jsonl_dir, &dir.join(format!("{}_reextracted_index", format)), &["--reextract"]);
+		indexes.push((read_documents(&dir.join(format!("{}_index", format))), read_documents(&dir.join(format!("{}_reextracted_index", format)))));
+	}
+	let (documents, reextracted) = &indexes[0];
+	assert_eq!(documents.len(), 2);
+	assert!(documents.iter().any(|d| d.contains("/docs/a%2Fb.html")));
+	assert_eq!(reextracted.len(), 2);
+	assert!(indexes.iter().all(|i| i == &indexes[0]));
+
+	std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn reads_externally_gzipped_jsonl() {
+	let dir = get_temp_dir("indexer_gzipped_jsonl");
+	let write_gzipped = |name: &str, page_count: usize| {
+		let pages: Vec<_> = (0..page_count)
+			.map(|i| get_page(&format!("https://fixture.example/docs/{}.html", i), &format!("Page {}", i), &format!("{} {}", LONG_TEXT.repeat(20), i)))
+			.collect();
+		let lines = write_jsonl(&dir.join(name).join("fixture.jsonl"), &pages);
+		// Compressed as a single stream, like the gzip tool does
+		let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+		encoder.write_all(&lines).unwrap();
+		std::fs::write(dir.join(name).join("fixture.jsonl.gz"), encoder.finish().unwrap()).unwrap();
+		std::fs::rename(dir.join(name).join("fixture.jsonl"), dir.join(format!("{}.jsonl", name))).unwrap();
+	};
+
+	// Small files are read like the ones written by the converter
+	write_gzipped("small", 3);
+	run_indexer(&dir.join("small"), &dir.join("small_index"), &[]);
+	std::fs::create_dir_all(dir.join("uncompressed")).unwrap();
+	std::fs::rename(dir.join("small.jsonl"), dir.join("uncompressed/fixture.jsonl")).unwrap();
+	run_indexer(&dir.join("uncompressed"), &dir.join("uncompressed_index"), &[]);
+	let documents = read_documents(&dir.join("small_index"));
+	assert_eq!(documents.len(), 3);
+	assert_eq!(documents, read_documents(&dir.join("uncompressed_index")));
+
+	// Reading pages from larger ones would mean decompressing them from the
+	// start for every page
+	write_gzipped("large", 2000);
+	let error = jsonl::read_urls(&dir.join("large/fixture.jsonl.gz"), |_, _, _| Ok(())).unwrap_err();
+	assert!(error.to_string().contains("single stream"));
+	let output = Command::new(env!("CARGO_BIN_EXE_indexer"))
+		.arg(dir.join("large")).arg(dir.join("large_index"))
+		.output().unwrap();
+	assert!(!output.status.success());
+
+	std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn reports_malformed_pages() {
+	let dir = get_temp_dir("indexer_malformed");
+	let output_dir = dir.join("output").join("fixture");
+	std::fs::create_dir_all(&output_dir).unwrap();
+	let page = get_page("https://fixture.example/docs/good.html", "Good", LONG_TEXT);
+	std::fs::write(output_dir.join("https:%2F%2Ffixture.example%2Fdocs%2Fgood.html.json"), page.to_string()).unwrap();
+	let malformed = "{\n\t\"url\": \"https://fixture.example/docs/bad.html\",\n\t\"title\": @nope\n}\n";
+	let malformed_path = output_dir.join("https:%2F%2Ffixture.example%2Fdocs%2Fbad.html.json");
+	std::fs::write(&malformed_path, malformed).unwrap();
+
+	// The malformed page is skipped and reported with where the error is
+	let report_path = dir.join("report.json");
+	run_indexer(&dir.join("output"), &dir.join("index"), &["--report", report_path.to_str().unwrap()]);
+	assert_eq!(read_documents(&dir.join("index")).len(), 1);
+	let report: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&report_path).unwrap()).unwrap();
+	let errors = report.as_array().unwrap();
+	assert_eq!(errors.len(), 1);
+	assert_eq!(errors[0]["path"], malformed_path.to_str().unwrap());
+	assert!(errors[0]["error"].as_str().unwrap().contains("line 3"));
+	let offset = errors[0]["offset"].as_u64().unwrap() as usize;
+	assert!(malformed[offset..].starts_with("@nope"), "{}", &malformed[offset..]);
+
+	// With --strict, the indexer stops at it instead
+	let output = Command::new(env!("CARGO_BIN_EXE_indexer"))
+		.arg("--strict")
+		.arg(dir.join("output")).arg(dir.join("strict_index"))
+		.output().unwrap();
+	assert!(!output.status.success());
+	assert!(String::from_utf8_lossy(&output.stderr).contains("bad.html"));
+
+	std::fs::remove_dir_all(dir).unwrap();
+}
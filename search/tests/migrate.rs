@@ -0,0 +1,67 @@
+use std::path::Path;
+use std::process::Command;
+use mini_search_engine::index;
+
+mod common;
+use common::{LONG_TEXT, get_page, get_temp_dir, read_documents, run_indexer, write_jsonl};
+
+fn run_migrate(old_index_dir: &Path, new_index_dir: &Path) {
+	let status = Command::new(env!("CARGO_BIN_EXE_migrate"))
+		.arg(old_index_dir).arg(new_index_dir)
+		.status().unwrap();
+	assert!(status.success());
+}
+
+fn read_manifest(index_dir: &Path) -> serde_json::Value {
+	serde_json::from_str(&std::fs::read_to_string(index_dir.join(index::MANIFEST_FILE)).unwrap()).unwrap()
+}
+
+#[test]
+fn migrate_keeps_index_settings() {
+	let dir = get_temp_dir("migrate");
+	let pages: Vec<_> = (0..3).map(|i| {
+		let mut page = get_page(&format!("https://fixture.example/docs/{}.html", i), &format!("Page {}", i), &format!("{} {}", LONG_TEXT, i));
+		page["links"] = serde_json::json!([format!("https://fixture.example/docs/{}.html", (i + 1) % 3)]);
+		page
+	}).collect();
+	write_jsonl(&dir.join("input/fixture.jsonl"), &pages);
+	let args = ["--global-page-rank", "--split-sections", "fixture", "--damping", "0.5", "--page-rank-iterations", "10"];
+	run_indexer(&dir.join("input"), &dir.join("fresh"), &args);
+
+	// A copy of the index which claims to have an older schema
+	std::fs::create_dir_all(dir.join("old")).unwrap();
+	for entry in std::fs::read_dir(dir.join("fresh")).unwrap() {
+		let path = entry.unwrap().path();
+		std::fs::copy(&path, dir.join("old").join(path.file_name().unwrap())).unwrap();
+	}
+	let mut old_manifest = read_manifest(&dir.join("old"));
+	old_manifest["schema_version"] = 1.into();
+	std::fs::write(dir.join("old").join(index::MANIFEST_FILE), old_manifest.to_string()).unwrap();
+
+	run_migrate(&dir.join("old"), &dir.join("migrated"));
+	assert_eq!(read_manifest(&dir.join("migrated")), read_manifest(&dir.join("fresh")));
+	assert_eq!(read_documents(&dir.join("migrated")), read_documents(&dir.join("fresh")));
+
+	std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn migrates_index_without_manifest() {
+	let dir = get_temp_dir("migrate_legacy");
+	write_jsonl(&dir.join("input/fixture.jsonl"), &[get_page("https://fixture.example/docs/", "Café", LONG_TEXT)]);
+	run_indexer(&dir.join("input"), &dir.join("old"), &[]);
+
+	// Indexes created before the manifest existed only had a list of domains
+	std::fs::remove_file(dir.join("old").join(index::MANIFEST_FILE)).unwrap();
+	std::fs::write(dir.join("old/domains.txt"), "fixture\n").unwrap();
+
+	run_migrate(&dir.join("old"), &dir.join("migrated"));
+	let manifest = index::IndexManifest::open(dir.join("migrated").to_str().unwrap()).unwrap();
+	assert_eq!(manifest.domains.iter().map(|d| d.name.as_str()).collect::<Vec<_>>(), ["fixture"]);
+	let default_analyzer = serde_json::to_value(index::AnalyzerConfig::default()).unwrap();
+	assert_eq!(serde_json::to_value(manifest.analyzer).unwrap(), default_analyzer);
+	run_indexer(&dir.join("input"), &dir.join("fresh"), &[]);
+	assert_eq!(read_documents(&dir.join("migrated")), read_documents(&dir.join("fresh")));
+
+	std::fs::remove_dir_all(dir).unwrap();
+}
@@ -1,7 +1,8 @@
 import scrapy, json, os, sys, argparse
+from datetime import datetime, timezone
 from scrapy.crawler import CrawlerProcess
 from urllib.parse import urlparse, urljoin
-from domains import domain_whitelists, domain_blacklists
+from domains import domain_whitelists, domain_blacklists, domain_limits
 
 def main():
 	global whitelist
@@ -24,9 +25,17 @@ def main():
 		print(f"unsupported domain {domain}", file=sys.stderr)
 		sys.exit(1)
 
+	# Same defaults as the Rust crawler
+	limits = domain_limits.get(domain, {})
 	process = CrawlerProcess(settings={
 		"LOG_LEVEL": "ERROR",
 		"REDIRECT_ENABLED": False,
+		"USER_AGENT": limits.get("user_agent", "mini_search_engine/0.1.0"),
+		"CONCURRENT_REQUESTS_PER_DOMAIN": limits.get("concurrency", 2),
+		"DOWNLOAD_DELAY": limits.get("delay", 0.5),
+		"RETRY_HTTP_CODES": [429, 503],
+		"RETRY_TIMES": limits.get("max_retries", 5),
+		"ROBOTSTXT_OBEY": limits.get("obey_robots", True),
 	})
 	process.crawl(MiniSearchEngineSpider)
 	process.start()
@@ -37,6 +46,20 @@ class MiniSearchEngineSpider(scrapy.Spider):
 	def __init__(self):
 		self.start_urls=whitelist
 		self.allowed_domains=[urlparse(whitelist[0]).netloc]
+		self.start_time = datetime.now(timezone.utc).isoformat()
+		self.write_state(False)
+
+	def closed(self, reason):
+		self.write_state(reason == "finished")
+
+	# Same format as the crawl state of the Rust crawler, which also has the
+	# frontier so that it can resume. The spider always starts over.
+	def write_state(self, complete):
+		state = {"start": self.start_time, "end": datetime.now(timezone.utc).isoformat() if complete else None, "complete": complete}
+		os.makedirs(output_dir, exist_ok=True)
+		with open(f"{output_dir}/crawl_state.json.tmp", "w") as f:
+			json.dump(state, f)
+		os.replace(f"{output_dir}/crawl_state.json.tmp", f"{output_dir}/crawl_state.json")
 
 	def parse(self, response):
 		if self.get_absolute_url(response) is None:
@@ -48,11 +71,13 @@ class MiniSearchEngineSpider(scrapy.Spider):
 			print(f"SKIP {response.url}: BAD CHARSET \"{content_type}\"")
 			return
 
-		links = set()
-		for href in response.css("a::attr(href)").getall():
-			url = self.get_absolute_url(response, href)
+		# Maps each link target to the anchor texts used for it
+		links = {}
+		for a in response.css("a[href]"):
+			url = self.get_absolute_url(response, a.attrib["href"])
 			if url is not None:
-				links.add(url)
+				text = " ".join(" ".join(a.css("::text, *::text").getall()).split())
+				links.setdefault(url, set()).add(text)
 
 		if not self.download(response, links):
 			print(f"SKIP {response.url}: NOT ENOUGH CONTENT")
@@ -64,7 +89,7 @@ class MiniSearchEngineSpider(scrapy.Spider):
 			yield response.follow(url, self.parse)
 
 	def download(self, response, links):
-		data = {"url": response.url, "domain": urlparse(response.url).netloc, "links": list(links)}
+		data = {"url": response.url, "domain": urlparse(response.url).netloc, "links": [{"url": url, "text": text} for url, texts in links.items() for text in texts]}
 		code_tags = "code tt pre kdb samp var".split(" ")
 		heading_tags = "title h1 h2 h3 h4 h5 h6".split(" ")
 		meta_selectors = [f"meta[name='{x}']::attr(content)" for x in ["keywords", "description"]]
@@ -81,12 +106,45 @@ class MiniSearchEngineSpider(scrapy.Spider):
 		if len(data["text"]) < 200:
 			return False
 
+		data["sections"] = self.get_sections(response, code_tags)
+
 		os.makedirs(output_dir, exist_ok=True)
 		filename = response.url.replace("/", "%2F")
 		with open(f"{output_dir}/{filename}.json", "w") as f:
 			json.dump(data, f)
+		# Kept so that the indexer can extract the page again with --reextract
+		with open(f"{output_dir}/{filename}.html", "wb") as f:
+			f.write(response.body)
 		return True
 
+	# Splits the body into sections starting at headings with an anchor, in
+	# the same format as the page itself
+	def get_sections(self, response, code_tags):
+		headings = " or ".join(f"self::h{i}" for i in range(1, 7))
+		code = " or ".join(f"ancestor::{t}" for t in code_tags)
+		nodes = response.xpath(f"//body//*[{headings}] | //body//text()[not(ancestor::script) and not(ancestor::style) and not(ancestor::*[{headings}])]")
+		sections = [{"id": "", "heading": "", "level": 0, "text": [], "code": []}]
+		for node in nodes:
+			if isinstance(node.root, str):
+				text = node.get().strip()
+				if text:
+					sections[-1]["text"].append(text)
+					if node.xpath(f"boolean({code})").get() == "1":
+						sections[-1]["code"].append(text)
+				continue
+			heading = " ".join(" ".join(node.xpath(".//text()").getall()).split())
+			anchor = node.xpath("@id | .//@id | preceding-sibling::*[1][self::a]/@name | preceding-sibling::*[1][self::a]/@id").get()
+			if anchor is None:
+				# Can't be linked to, so it's just part of the current section
+				sections[-1]["text"].append(heading)
+				continue
+			level = int(node.xpath("name()").get()[1])
+			sections.append({"id": anchor, "heading": heading, "level": level, "text": [], "code": []})
+		for section in sections:
+			section["text"] = "\n".join(section["text"])
+			section["code"] = "\n".join(section["code"])
+		return [s for s in sections if s["id"] or s["text"]]
+
 	def get_absolute_url(self, response, url=""):
 		# Basic check for special HTML hrefs
 		if any(url.startswith(p) for p in ["#", "mailto:", "data:", "javascript:"]):